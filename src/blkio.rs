@@ -0,0 +1,503 @@
+//! The io (v2) and blkio (v1) controllers.
+
+use std::collections::HashMap;
+use std::io::FileType;
+use std::io::fs::{self, PathExtensions};
+
+use {Controller, Device, Error, FlatKeyed, Limit, Result, Version};
+use parse;
+
+flat_keyed! {
+    /// Per-device IO counters.
+    pub struct IoStat {
+        /// Bytes read
+        rbytes,
+        /// Bytes written
+        wbytes,
+        /// Read operations
+        rios,
+        /// Write operations
+        wios,
+        /// Bytes discarded
+        dbytes,
+        /// Discard operations
+        dios
+    }
+}
+
+/// Per-device throttling limits, unlimited by default.
+#[derive(Clone, Copy, PartialEq, Eq, Show, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IoLimits {
+    /// Read bytes per second
+    pub rbps: Limit,
+    /// Written bytes per second
+    pub wbps: Limit,
+    /// Read operations per second
+    pub riops: Limit,
+    /// Write operations per second
+    pub wiops: Limit,
+}
+
+/// One of the v1 blkio throttling files.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Throttle {
+    /// Read bytes per second, `blkio.throttle.read_bps_device`
+    ReadBps,
+    /// Written bytes per second, `blkio.throttle.write_bps_device`
+    WriteBps,
+    /// Read operations per second, `blkio.throttle.read_iops_device`
+    ReadIops,
+    /// Write operations per second, `blkio.throttle.write_iops_device`
+    WriteIops,
+}
+
+impl Throttle {
+    pub fn key(&self) -> &'static [u8] {
+        match *self {
+            Throttle::ReadBps => b"blkio.throttle.read_bps_device",
+            Throttle::WriteBps => b"blkio.throttle.write_bps_device",
+            Throttle::ReadIops => b"blkio.throttle.read_iops_device",
+            Throttle::WriteIops => b"blkio.throttle.write_iops_device",
+        }
+    }
+}
+
+/// The whole disk holding a block device or a file, for the per-device files.
+///
+/// A block device node stands for itself, any other path for the device its filesystem is
+/// on. Partitions are resolved to their disk through sysfs, since limits and weights are kept
+/// per disk. Filesystems without a block device of their own, like tmpfs or btrfs (whose
+/// `st_dev` is an anonymous device), are `InvalidValue`.
+pub fn device_of(path: &Path) -> Result<Device> {
+    let stat = try!(fs::stat(path).map_err(|e| Error::from_io(path, e)));
+    let dev = if stat.kind == FileType::BlockSpecial {
+        stat.unstable.rdev
+    } else {
+        stat.unstable.device
+    };
+    // glibc's encoding of dev_t
+    let dev = Device {
+        major: ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff),
+        minor: (dev & 0xff) | ((dev >> 12) & !0xff),
+    };
+    if dev.major == 0 {
+        return Err(Error::InvalidValue(path.clone(),
+                                       format!("not on a block device ({})", dev)));
+    }
+
+    let sys = Path::new(format!("/sys/dev/block/{}", dev));
+    if !sys.join("partition").exists() {
+        return Ok(dev);
+    }
+    // the partition's directory is inside its disk's
+    let target = try!(fs::readlink(&sys).map_err(|e| Error::from_io(&sys, e)));
+    let disk = Path::new("/sys/dev/block").join(target).dir_path().join("dev");
+    let s = try!(::read_file(&disk));
+    match Device::parse(s.as_slice().trim()) {
+        Some(d) => Ok(d),
+        None => Err(Error::InvalidValue(disk, format!("{:?}", s.as_slice().trim()))),
+    }
+}
+
+/// Whether the kernel or the user picks iocost's parameters for a device.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CostControl {
+    Auto,
+    User,
+}
+
+impl CostControl {
+    fn parse(s: &str) -> Option<CostControl> {
+        match s {
+            "auto" => Some(CostControl::Auto),
+            "user" => Some(CostControl::User),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            CostControl::Auto => "auto",
+            CostControl::User => "user",
+        }
+    }
+}
+
+/// A device's line of the root's `io.cost.qos`: when iocost considers the device saturated,
+/// and how far it may scale the device's capacity.
+#[derive(Clone, Copy, PartialEq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IoCostQos {
+    pub enable: bool,
+    pub ctrl: CostControl,
+    /// The percentile of read latencies that `rlat` applies to
+    pub rpct: f64,
+    /// Read latency target, in microseconds
+    pub rlat: u64,
+    /// The percentile of write latencies that `wlat` applies to
+    pub wpct: f64,
+    /// Write latency target, in microseconds
+    pub wlat: u64,
+    /// Lowest scaling of the device's capacity, as a percentage
+    pub min: f64,
+    /// Highest scaling of the device's capacity, as a percentage
+    pub max: f64,
+}
+
+/// A device's line of the root's `io.cost.model`: the linear cost model of the device.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IoCostModel {
+    pub ctrl: CostControl,
+    /// Sequential read bytes per second
+    pub rbps: u64,
+    /// Sequential 4k reads per second
+    pub rseqiops: u64,
+    /// Random 4k reads per second
+    pub rrandiops: u64,
+    /// Sequential write bytes per second
+    pub wbps: u64,
+    /// Sequential 4k writes per second
+    pub wseqiops: u64,
+    /// Random 4k writes per second
+    pub wrandiops: u64,
+}
+
+/// Typed access to the v2 io controller.
+pub struct IoController {
+    inner: Controller,
+}
+
+impl IoController {
+    /// Wrap a controller handle for the v2 io controller.
+    pub fn new(inner: Controller) -> IoController {
+        IoController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// Per-device counters from `io.stat`.
+    pub fn stat(&self) -> Result<HashMap<Device, IoStat>> {
+        let stat = try!(self.inner.get_nested_keyed(b"io.stat"));
+        Ok(stat.iter().map(|(&dev, fields)| (dev, FlatKeyed::from_map(fields))).collect())
+    }
+
+    /// Per-device limits from `io.max`. Devices without limits aren't listed.
+    pub fn max(&self) -> Result<HashMap<Device, IoLimits>> {
+        let s = try!(self.inner.get(b"io.max"));
+        let raw = match parse::nested_keyed_raw(s.as_slice()) {
+            Some(raw) => raw,
+            None => return Err(self.inner.invalid_value(b"io.max", s.as_slice())),
+        };
+
+        let mut map = HashMap::new();
+        for (dev, fields) in raw.iter() {
+            let field = |&: name: &str| -> Result<Limit> {
+                match fields.get(name).map(|v| Limit::parse(v.as_slice())) {
+                    None => Ok(Limit::Max),
+                    Some(Some(l)) => Ok(l),
+                    Some(None) => Err(self.inner.invalid_value(b"io.max", s.as_slice())),
+                }
+            };
+            map.insert(*dev, IoLimits {
+                rbps: try!(field("rbps")),
+                wbps: try!(field("wbps")),
+                riops: try!(field("riops")),
+                wiops: try!(field("wiops")),
+            });
+        }
+        Ok(map)
+    }
+
+    /// Set the limits for a device in `io.max`.
+    pub fn set_max(&self, dev: Device, limits: IoLimits) -> Result<()> {
+        let value = format!("{} rbps={} wbps={} riops={} wiops={}", dev,
+                            limits.rbps, limits.wbps, limits.riops, limits.wiops);
+        self.inner.set(b"io.max", value.as_slice())
+    }
+
+    /// Per-device latency targets from `io.latency`, in microseconds. Devices without one aren't
+    /// listed.
+    pub fn latency(&self) -> Result<HashMap<Device, u64>> {
+        let s = try!(self.inner.get(b"io.latency"));
+        let raw = match parse::nested_keyed_raw(s.as_slice()) {
+            Some(raw) => raw,
+            None => return Err(self.inner.invalid_value(b"io.latency", s.as_slice())),
+        };
+        let mut map = HashMap::new();
+        for (dev, fields) in raw.iter() {
+            match fields.get("target").and_then(|t| t.as_slice().parse()) {
+                Some(t) => { map.insert(*dev, t); },
+                None => return Err(self.inner.invalid_value(b"io.latency", s.as_slice())),
+            }
+        }
+        Ok(map)
+    }
+
+    /// Set the latency target of a device in microseconds, `Max` to remove it.
+    pub fn set_latency(&self, dev: Device, target_us: Limit) -> Result<()> {
+        let value = format!("{} target={}", dev, target_us);
+        self.inner.set(b"io.latency", value.as_slice())
+    }
+
+    /// The iocost QoS parameters of each device, from the root's `io.cost.qos`.
+    pub fn cost_qos(&self) -> Result<HashMap<Device, IoCostQos>> {
+        let s = try!(self.inner.get(b"io.cost.qos"));
+        let raw = match parse::nested_keyed_raw(s.as_slice()) {
+            Some(raw) => raw,
+            None => return Err(self.inner.invalid_value(b"io.cost.qos", s.as_slice())),
+        };
+        let mut map = HashMap::new();
+        for (dev, f) in raw.iter() {
+            let get = |&: name: &str| f.get(name).map(|v| v.as_slice());
+            let pct = |&: name: &str| get(name).and_then(|v| v.parse::<f64>());
+            let us = |&: name: &str| get(name).and_then(|v| v.parse::<u64>());
+            let qos = match (get("enable"), get("ctrl").and_then(CostControl::parse), pct("rpct"),
+                             us("rlat"), pct("wpct"), us("wlat"), pct("min"), pct("max")) {
+                (Some(enable), Some(ctrl), Some(rpct), Some(rlat), Some(wpct), Some(wlat),
+                 Some(min), Some(max)) => IoCostQos {
+                    enable: enable == "1",
+                    ctrl: ctrl,
+                    rpct: rpct,
+                    rlat: rlat,
+                    wpct: wpct,
+                    wlat: wlat,
+                    min: min,
+                    max: max,
+                },
+                _ => return Err(self.inner.invalid_value(b"io.cost.qos", s.as_slice())),
+            };
+            map.insert(*dev, qos);
+        }
+        Ok(map)
+    }
+
+    /// Set the iocost QoS parameters of a device. With `CostControl::Auto` the kernel
+    /// picks everything but `enable` itself.
+    pub fn set_cost_qos(&self, dev: Device, qos: IoCostQos) -> Result<()> {
+        let value = match qos.ctrl {
+            CostControl::Auto => format!("{} enable={} ctrl=auto", dev, qos.enable as u8),
+            CostControl::User => format!(
+                "{} enable={} ctrl=user rpct={:.2} rlat={} wpct={:.2} wlat={} min={:.2} max={:.2}",
+                dev, qos.enable as u8, qos.rpct, qos.rlat, qos.wpct, qos.wlat, qos.min, qos.max),
+        };
+        self.inner.set(b"io.cost.qos", value.as_slice())
+    }
+
+    /// The iocost model of each device, from the root's `io.cost.model`.
+    pub fn cost_model(&self) -> Result<HashMap<Device, IoCostModel>> {
+        let s = try!(self.inner.get(b"io.cost.model"));
+        let raw = match parse::nested_keyed_raw(s.as_slice()) {
+            Some(raw) => raw,
+            None => return Err(self.inner.invalid_value(b"io.cost.model", s.as_slice())),
+        };
+        let mut map = HashMap::new();
+        for (dev, f) in raw.iter() {
+            let get = |&: name: &str| f.get(name).map(|v| v.as_slice());
+            let n = |&: name: &str| get(name).and_then(|v| v.parse::<u64>());
+            if get("model").map_or(false, |m| m != "linear") {
+                return Err(self.inner.invalid_value(b"io.cost.model", s.as_slice()));
+            }
+            let model = match (get("ctrl").and_then(CostControl::parse), n("rbps"),
+                               n("rseqiops"), n("rrandiops"), n("wbps"), n("wseqiops"),
+                               n("wrandiops")) {
+                (Some(ctrl), Some(rbps), Some(rseqiops), Some(rrandiops), Some(wbps),
+                 Some(wseqiops), Some(wrandiops)) => IoCostModel {
+                    ctrl: ctrl,
+                    rbps: rbps,
+                    rseqiops: rseqiops,
+                    rrandiops: rrandiops,
+                    wbps: wbps,
+                    wseqiops: wseqiops,
+                    wrandiops: wrandiops,
+                },
+                _ => return Err(self.inner.invalid_value(b"io.cost.model", s.as_slice())),
+            };
+            map.insert(*dev, model);
+        }
+        Ok(map)
+    }
+
+    /// Set the iocost model of a device. With `CostControl::Auto` the kernel's own
+    /// model is used and the numbers are ignored.
+    pub fn set_cost_model(&self, dev: Device, model: IoCostModel) -> Result<()> {
+        let value = match model.ctrl {
+            CostControl::Auto => format!("{} ctrl=auto", dev),
+            CostControl::User => format!(
+                "{} ctrl=user model=linear rbps={} rseqiops={} rrandiops={} wbps={} wseqiops={} \
+                 wrandiops={}", dev, model.rbps, model.rseqiops, model.rrandiops, model.wbps,
+                model.wseqiops, model.wrandiops),
+        };
+        self.inner.set(b"io.cost.model", value.as_slice())
+    }
+
+    /// Set the limits in `io.max` for the disk holding a device node or a file, as found by
+    /// `device_of`.
+    pub fn set_max_for(&self, path: &Path, limits: IoLimits) -> Result<()> {
+        self.set_max(try!(device_of(path)), limits)
+    }
+}
+
+/// Typed access to the v1 blkio controller.
+pub struct BlkioController {
+    inner: Controller,
+}
+
+impl BlkioController {
+    /// Wrap a controller handle for the v1 blkio controller.
+    pub fn new(inner: Controller) -> BlkioController {
+        BlkioController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// Per-device counters, from `blkio.throttle.io_service_bytes` and `io_serviced`.
+    ///
+    /// These count IO at the throttling layer, so they are kept whichever scheduler is in use.
+    pub fn stat(&self) -> Result<HashMap<Device, IoStat>> {
+        let bytes = try!(self.inner.get_nested_keyed(b"blkio.throttle.io_service_bytes"));
+        let ios = try!(self.inner.get_nested_keyed(b"blkio.throttle.io_serviced"));
+
+        let mut map: HashMap<Device, IoStat> = HashMap::new();
+        for (dev, fields) in bytes.iter() {
+            let stat = map.entry(*dev).get().unwrap_or_else(|v| v.insert(Default::default()));
+            stat.rbytes = fields.get("Read").map(|&v| v).unwrap_or(0);
+            stat.wbytes = fields.get("Write").map(|&v| v).unwrap_or(0);
+            stat.dbytes = fields.get("Discard").map(|&v| v).unwrap_or(0);
+        }
+        for (dev, fields) in ios.iter() {
+            let stat = map.entry(*dev).get().unwrap_or_else(|v| v.insert(Default::default()));
+            stat.rios = fields.get("Read").map(|&v| v).unwrap_or(0);
+            stat.wios = fields.get("Write").map(|&v| v).unwrap_or(0);
+            stat.dios = fields.get("Discard").map(|&v| v).unwrap_or(0);
+        }
+        Ok(map)
+    }
+
+    /// The limits in one of the throttling files, by device. Devices without a limit aren't
+    /// listed.
+    pub fn throttle(&self, kind: Throttle) -> Result<HashMap<Device, u64>> {
+        let key = kind.key();
+        let s = try!(self.inner.get(key));
+        let mut map = HashMap::new();
+        for line in s.as_slice().lines().filter(|l| !l.trim().is_empty()) {
+            let words: Vec<&str> = line.words().collect();
+            match (words.len(), words.get(0).and_then(|w| Device::parse(*w)),
+                   words.get(1).and_then(|w| w.parse::<u64>())) {
+                (2, Some(dev), Some(limit)) => { map.insert(dev, limit); },
+                _ => return Err(self.inner.invalid_value(key, s.as_slice())),
+            }
+        }
+        Ok(map)
+    }
+
+    /// Set or, with `Max`, remove the limit on a device in one of the throttling files.
+    ///
+    /// The kernel takes a limit of 0 to mean none, so `Value(0)` is refused as `InvalidValue`
+    /// rather than quietly lifting the limit.
+    pub fn set_throttle_limit(&self, kind: Throttle, dev: Device, limit: Limit) -> Result<()> {
+        let key = kind.key();
+        if limit == Limit::Value(0) || dev.major == 0 {
+            return Err(Error::InvalidValue(self.inner.path().join(key), format!(
+                "invalid throttle {} {}", dev, limit)));
+        }
+        // writing 0 removes a limit
+        let value = format!("{} {}", dev, limit.value().unwrap_or(0));
+        self.inner.set(key, value.as_slice())
+    }
+
+    /// Set the throttling limits for a device. Unlimited fields remove the existing limit.
+    pub fn set_throttle(&self, dev: Device, limits: IoLimits) -> Result<()> {
+        let files = [
+            (Throttle::ReadBps, limits.rbps),
+            (Throttle::WriteBps, limits.wbps),
+            (Throttle::ReadIops, limits.riops),
+            (Throttle::WriteIops, limits.wiops),
+        ];
+        for &(kind, limit) in files.iter() {
+            try!(self.set_throttle_limit(kind, dev, limit));
+        }
+        Ok(())
+    }
+}
+
+/// A weight file: its name, where per-device weights go, the range it takes, and whether the
+/// default is written as `default <n>` rather than a bare number.
+struct WeightFile {
+    key: &'static str,
+    device_key: &'static str,
+    min: u64,
+    max: u64,
+    default_prefix: bool,
+}
+
+static V1_WEIGHTS: [WeightFile; 2] = [
+    WeightFile { key: "blkio.bfq.weight", device_key: "blkio.bfq.weight_device", min: 1,
+                 max: 1000, default_prefix: false },
+    WeightFile { key: "blkio.weight", device_key: "blkio.weight_device", min: 10, max: 1000,
+                 default_prefix: false },
+];
+
+static V2_WEIGHTS: [WeightFile; 2] = [
+    WeightFile { key: "io.weight", device_key: "io.weight", min: 1, max: 10000,
+                 default_prefix: true },
+    WeightFile { key: "io.bfq.weight", device_key: "io.bfq.weight", min: 1, max: 1000,
+                 default_prefix: true },
+];
+
+impl Controller {
+    /// Set the proportional IO weight of this cgroup, by default and for some devices.
+    ///
+    /// Which file takes it depends on the version and on what the kernel provides: `io.weight`
+    /// (iocost) and `io.bfq.weight` in v2, `blkio.bfq.weight`/`blkio.bfq.weight_device`
+    /// and the older CFQ `blkio.weight`/`blkio.weight_device` in v1. Every one that exists is
+    /// written, so the weight holds whichever scheduler each disk uses.
+    ///
+    /// Weights are on the `io.weight` scale, from 1 to 10000; one outside it is `InvalidValue`
+    /// and nothing is written. Files with a narrower range get the weight clamped to it:
+    /// `blkio.weight` gets 10 to 1000, and the BFQ files 1 to 1000. Fails with `KeyNotFound`
+    /// if there is no weight file.
+    pub fn set_io_weight(&self, default: Option<u64>, per_device: &[(Device, u64)])
+                         -> Result<()> {
+        let files = match self.version() {
+            Version::V1 => &V1_WEIGHTS,
+            Version::V2 => &V2_WEIGHTS,
+        };
+        let weights = default.into_iter().chain(per_device.iter().map(|&(_, w)| w));
+        for w in weights.filter(|&w| w < 1 || w > 10000) {
+            return Err(Error::InvalidValue(self.path().join(files[0].key),
+                                           format!("weight {} is not from 1 to 10000", w)));
+        }
+        let mut found = false;
+        for file in files.iter().filter(|f| self.path().join(f.key).exists()) {
+            found = true;
+            let clamp = |&: w: u64| if w < file.min { file.min } else if w > file.max { file.max }
+                                    else { w };
+            if let Some(w) = default {
+                let value = if file.default_prefix {
+                    format!("default {}", clamp(w))
+                } else {
+                    format!("{}", clamp(w))
+                };
+                try!(self.set(file.key.as_bytes(), value.as_slice()));
+            }
+            for &(dev, w) in per_device.iter() {
+                let value = format!("{} {}", dev, clamp(w));
+                try!(self.set(file.device_key.as_bytes(), value.as_slice()));
+            }
+        }
+        if !found {
+            return Err(Error::KeyNotFound(self.path().join(files[0].key)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+//! Device access control for the unified hierarchy, with BPF.
+//!
+//! v2 has no `devices.allow`; instead, a `BPF_PROG_TYPE_CGROUP_DEVICE` program attached to a
+//! cgroup decides on every device access by its members. This module builds such programs from
+//! the same `DeviceRule`s as the v1 devices controller, or loads prebuilt ones.
+
+use std::ffi::CString;
+use libc::c_int;
+
+use {CgroupFd, Error, Result};
+use devices::{DeviceRule, DeviceType};
+use sys::{bpf, Fd};
+
+const BPF_PROG_LOAD: c_int = 5;
+const BPF_PROG_ATTACH: c_int = 8;
+const BPF_PROG_DETACH: c_int = 9;
+const BPF_PROG_TYPE_CGROUP_DEVICE: u32 = 15;
+const BPF_CGROUP_DEVICE: u32 = 6;
+const BPF_F_ALLOW_MULTI: u32 = 2;
+
+// struct bpf_cgroup_dev_ctx: access_type is (access << 16) | type
+const DEV_BLOCK: i32 = 1;
+const DEV_CHAR: i32 = 2;
+const ACC_MKNOD: i32 = 1;
+const ACC_READ: i32 = 2;
+const ACC_WRITE: i32 = 4;
+
+/// Bytes of verifier log to ask for when loading fails.
+const LOG_SIZE: usize = 65536;
+
+/// One BPF instruction, `struct bpf_insn`.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub struct Insn {
+    pub code: u8,
+    /// Destination register in the low nibble, source in the high one
+    pub regs: u8,
+    pub off: i16,
+    pub imm: i32,
+}
+
+fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Insn {
+    Insn { code: code, regs: dst | (src << 4), off: off, imm: imm }
+}
+
+// the few opcodes the generated programs use
+fn ldx_w(dst: u8, src: u8, off: i16) -> Insn { insn(0x61, dst, src, off, 0) }
+fn and32(dst: u8, imm: i32) -> Insn { insn(0x54, dst, 0, 0, imm) }
+fn rsh32(dst: u8, imm: i32) -> Insn { insn(0x74, dst, 0, 0, imm) }
+fn mov32_reg(dst: u8, src: u8) -> Insn { insn(0xbc, dst, src, 0, 0) }
+fn mov64(dst: u8, imm: i32) -> Insn { insn(0xb7, dst, 0, 0, imm) }
+fn jne(dst: u8, imm: i32, off: i16) -> Insn { insn(0x55, dst, 0, off, imm) }
+fn jne_reg(dst: u8, src: u8, off: i16) -> Insn { insn(0x5d, dst, src, off, 0) }
+fn exit() -> Insn { insn(0x95, 0, 0, 0, 0) }
+
+/// The instructions of a program allowing exactly the accesses `rules` cover, and denying all
+/// others.
+pub fn allow_list(rules: &[DeviceRule]) -> Vec<Insn> {
+    // r2 = type, r3 = access, r4 = major, r5 = minor
+    let mut prog = vec![
+        ldx_w(2, 1, 0),
+        and32(2, 0xffff),
+        ldx_w(3, 1, 0),
+        rsh32(3, 16),
+        ldx_w(4, 1, 4),
+        ldx_w(5, 1, 8),
+    ];
+
+    for rule in rules.iter() {
+        let access = (if rule.mknod { ACC_MKNOD } else { 0 }) |
+                     (if rule.read { ACC_READ } else { 0 }) |
+                     (if rule.write { ACC_WRITE } else { 0 });
+        if access == 0 {
+            continue;
+        }
+
+        // each check jumps past the rest of the block on a mismatch
+        let mut block = Vec::new();
+        match rule.kind {
+            DeviceType::All => { },
+            DeviceType::Block => block.push(jne(2, DEV_BLOCK, 0)),
+            DeviceType::Char => block.push(jne(2, DEV_CHAR, 0)),
+        }
+        if access != ACC_MKNOD | ACC_READ | ACC_WRITE {
+            // the requested access has to be a subset of what's allowed
+            block.push(mov32_reg(1, 3));
+            block.push(and32(1, access));
+            block.push(jne_reg(1, 3, 0));
+        }
+        if let Some(major) = rule.major {
+            block.push(jne(4, major as i32, 0));
+        }
+        if let Some(minor) = rule.minor {
+            block.push(jne(5, minor as i32, 0));
+        }
+        block.push(mov64(0, 1));
+        block.push(exit());
+
+        let len = block.len();
+        for (i, ins) in block.iter_mut().enumerate() {
+            if ins.code == 0x55 || ins.code == 0x5d {
+                ins.off = (len - i - 1) as i16;
+            }
+        }
+        prog.extend(block.into_iter());
+    }
+
+    prog.push(mov64(0, 0));
+    prog.push(exit());
+    prog
+}
+
+#[repr(C)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+}
+
+#[repr(C)]
+struct ProgAttachAttr {
+    target_fd: u32,
+    attach_bpf_fd: u32,
+    attach_type: u32,
+    attach_flags: u32,
+}
+
+/// A loaded device program. Attached programs stay in place after this is dropped; the kernel
+/// unloads a program once it is neither open nor attached anywhere.
+pub struct DeviceProgram {
+    fd: Fd,
+}
+
+impl DeviceProgram {
+    /// Build and load a program allowing the accesses `rules` cover.
+    pub fn allow(rules: &[DeviceRule]) -> Result<DeviceProgram> {
+        DeviceProgram::load(allow_list(rules).as_slice())
+    }
+
+    /// Load a prebuilt `BPF_PROG_TYPE_CGROUP_DEVICE` program. A program the verifier rejects
+    /// fails with `InvalidValue`, carrying the verifier's log.
+    pub fn load(insns: &[Insn]) -> Result<DeviceProgram> {
+        let path = Path::new("BPF_PROG_LOAD");
+        let license = CString::from_slice(b"GPL");
+        let mut attr = ProgLoadAttr {
+            prog_type: BPF_PROG_TYPE_CGROUP_DEVICE,
+            insn_cnt: insns.len() as u32,
+            insns: insns.as_ptr() as u64,
+            license: license.as_ptr() as u64,
+            log_level: 0,
+            log_size: 0,
+            log_buf: 0,
+            kern_version: 0,
+            prog_flags: 0,
+        };
+        let fd = unsafe { bpf(BPF_PROG_LOAD, &mut attr) };
+        if fd >= 0 {
+            return Ok(DeviceProgram { fd: try!(Fd::from_raw(fd as c_int, &path)) });
+        }
+        let err = Error::last_os_error(&path);
+
+        // load it again, this time with the verifier saying what it didn't like
+        let mut log = Vec::from_elem(LOG_SIZE, 0u8);
+        attr.log_level = 1;
+        attr.log_size = LOG_SIZE as u32;
+        attr.log_buf = log.as_mut_ptr() as u64;
+        let fd = unsafe { bpf(BPF_PROG_LOAD, &mut attr) };
+        if fd >= 0 {
+            return Ok(DeviceProgram { fd: try!(Fd::from_raw(fd as c_int, &path)) });
+        }
+        let len = log.iter().position(|&b| b == 0).unwrap_or(log.len());
+        if len == 0 {
+            return Err(err);
+        }
+        Err(Error::InvalidValue(path, String::from_utf8_lossy(&log[..len]).into_owned()))
+    }
+
+    /// Attach the program to a cgroup, alongside any programs already attached (which also all
+    /// have to allow an access).
+    pub fn attach(&self, cgroup: &CgroupFd) -> Result<()> {
+        let mut attr = ProgAttachAttr {
+            target_fd: cgroup.raw() as u32,
+            attach_bpf_fd: self.fd.raw() as u32,
+            attach_type: BPF_CGROUP_DEVICE,
+            attach_flags: BPF_F_ALLOW_MULTI,
+        };
+        if unsafe { bpf(BPF_PROG_ATTACH, &mut attr) } < 0 {
+            return Err(Error::last_os_error(cgroup.path()));
+        }
+        Ok(())
+    }
+
+    /// Detach the program from a cgroup.
+    pub fn detach(&self, cgroup: &CgroupFd) -> Result<()> {
+        let mut attr = ProgAttachAttr {
+            target_fd: cgroup.raw() as u32,
+            attach_bpf_fd: self.fd.raw() as u32,
+            attach_type: BPF_CGROUP_DEVICE,
+            attach_flags: 0,
+        };
+        if unsafe { bpf(BPF_PROG_DETACH, &mut attr) } < 0 {
+            return Err(Error::last_os_error(cgroup.path()));
+        }
+        Ok(())
+    }
+
+    /// The program's descriptor.
+    pub fn fd(&self) -> c_int {
+        self.fd.raw()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use devices::{DeviceRule, DeviceType};
+    use super::{allow_list, and32, exit, insn, jne, jne_reg, ldx_w, mov32_reg, mov64, rsh32,
+                Insn};
+
+    fn rule(s: &str) -> DeviceRule {
+        DeviceRule::parse(s).unwrap()
+    }
+
+    /// Loading type, access, major and minor into r2 to r5.
+    fn prologue() -> Vec<Insn> {
+        vec![ldx_w(2, 1, 0), and32(2, 0xffff), ldx_w(3, 1, 0), rsh32(3, 16), ldx_w(4, 1, 4),
+             ldx_w(5, 1, 8)]
+    }
+
+    fn program(blocks: &[Insn]) -> Vec<Insn> {
+        let mut prog = prologue();
+        prog.push_all(blocks);
+        prog.push(mov64(0, 0));
+        prog.push(exit());
+        prog
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(mem::size_of::<Insn>(), 8);
+        assert_eq!(insn(0x61, 2, 1, 4, 0), Insn { code: 0x61, regs: 0x12, off: 4, imm: 0 });
+        assert_eq!(ldx_w(5, 1, 8), Insn { code: 0x61, regs: 0x15, off: 8, imm: 0 });
+        assert_eq!(and32(2, 0xffff), Insn { code: 0x54, regs: 0x02, off: 0, imm: 0xffff });
+        assert_eq!(rsh32(3, 16), Insn { code: 0x74, regs: 0x03, off: 0, imm: 16 });
+        assert_eq!(mov32_reg(1, 3), Insn { code: 0xbc, regs: 0x31, off: 0, imm: 0 });
+        assert_eq!(mov64(0, 1), Insn { code: 0xb7, regs: 0x00, off: 0, imm: 1 });
+        assert_eq!(jne(4, 8, 2), Insn { code: 0x55, regs: 0x04, off: 2, imm: 8 });
+        assert_eq!(jne_reg(1, 3, 2), Insn { code: 0x5d, regs: 0x31, off: 2, imm: 0 });
+        assert_eq!(exit(), Insn { code: 0x95, regs: 0, off: 0, imm: 0 });
+    }
+
+    #[test]
+    fn deny_all() {
+        assert_eq!(allow_list(&[]), program(&[]));
+        // a rule granting nothing adds nothing
+        assert_eq!(allow_list(&[DeviceRule::new(DeviceType::All)]), program(&[]));
+    }
+
+    #[test]
+    fn wildcard() {
+        assert_eq!(allow_list(&[rule("a *:* rwm")]), program(&[mov64(0, 1), exit()]));
+    }
+
+    #[test]
+    fn char_device() {
+        // every mismatch jumps past the block's last instruction
+        assert_eq!(allow_list(&[rule("c 1:3 rwm")]), program(&[
+            jne(2, 2, 4),
+            jne(4, 1, 3),
+            jne(5, 3, 2),
+            mov64(0, 1),
+            exit(),
+        ]));
+    }
+
+    #[test]
+    fn access_subset() {
+        // denied unless access & (read | write) == access
+        assert_eq!(allow_list(&[rule("a *:* rw")]), program(&[
+            mov32_reg(1, 3),
+            and32(1, 6),
+            jne_reg(1, 3, 2),
+            mov64(0, 1),
+            exit(),
+        ]));
+    }
+
+    #[test]
+    fn several_rules() {
+        assert_eq!(allow_list(&[rule("b 8:* m"), rule("c *:5 r")]), program(&[
+            jne(2, 1, 6),
+            mov32_reg(1, 3),
+            and32(1, 1),
+            jne_reg(1, 3, 3),
+            jne(4, 8, 2),
+            mov64(0, 1),
+            exit(),
+            jne(2, 2, 6),
+            mov32_reg(1, 3),
+            and32(1, 2),
+            jne_reg(1, 3, 3),
+            jne(5, 5, 2),
+            mov64(0, 1),
+            exit(),
+        ]));
+    }
+}
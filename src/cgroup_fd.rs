@@ -0,0 +1,61 @@
+//! Handles on cgroup directories that outlive renames.
+
+use libc;
+
+use {Controller, Result};
+use sys::{self, Fd};
+
+/// An `O_PATH` descriptor for a cgroup directory. Files are opened relative to it, so it keeps
+/// referring to the same cgroup if it's moved, and reads and writes fail cleanly once it's
+/// removed rather than reaching whatever takes its place.
+///
+/// This is also what `clone3` (see `CgroupCommandExt::spawn_into_cgroup`) and BPF attachment
+/// identify a cgroup by.
+pub struct CgroupFd {
+    fd: Fd,
+}
+
+impl CgroupFd {
+    /// Open a cgroup directory.
+    pub fn open(path: &Path) -> Result<CgroupFd> {
+        let fd = try!(Fd::open(path, sys::O_PATH | sys::O_DIRECTORY));
+        Ok(CgroupFd { fd: fd })
+    }
+
+    /// Read a control file.
+    pub fn read(&self, key: &[u8]) -> Result<String> {
+        let file = try!(self.fd.openat(key, libc::O_RDONLY));
+        let mut contents = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            match try!(file.read(&mut buf)) {
+                0 => break,
+                n => contents.push_all(&buf[..n]),
+            }
+        }
+        Ok(String::from_utf8_lossy(contents.as_slice()).into_owned())
+    }
+
+    /// Write a control file in a single write, like `Controller::set`.
+    pub fn write(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let file = try!(self.fd.openat(key, libc::O_WRONLY | libc::O_TRUNC));
+        file.write(value)
+    }
+
+    /// The raw descriptor, still owned by this `CgroupFd`.
+    pub fn raw(&self) -> libc::c_int {
+        self.fd.raw()
+    }
+
+    /// The path the directory had when it was opened.
+    pub fn path(&self) -> &Path {
+        self.fd.path()
+    }
+}
+
+impl Controller {
+    /// Open an `O_PATH` handle on this cgroup's directory.
+    pub fn open_fd(&self) -> Result<CgroupFd> {
+        CgroupFd::open(self.path())
+    }
+}
@@ -0,0 +1,359 @@
+//! Starting processes in a cgroup.
+
+use std::ffi::CString;
+use std::io::pipe::PipeStream;
+use std::io::process::{Command, Process, ProcessExit, StdioContainer};
+use std::mem;
+use std::os;
+use std::ptr;
+use libc::{self, c_char, c_int};
+
+use {CgroupFd, Controller, Error, Result, Version};
+use sys::{self, Fd};
+
+/// Spawning a `Command` straight into a cgroup.
+pub trait CgroupCommandExt {
+    /// Spawn the command and move it into `cgroup` right away, through `cgroup.procs`.
+    ///
+    /// The move happens after the fork, so the child may run briefly (and exec) in this
+    /// process's cgroup first; anything it forks in that window stays behind. If the move
+    /// fails the child is killed and the error returned. `spawn_into_cgroup` has no such
+    /// window, on kernels that support it.
+    fn spawn_in(&self, cgroup: &Controller) -> Result<Process>;
+
+    /// Spawn the command already inside the v2 cgroup `cgroup`, with `clone3` and
+    /// `CLONE_INTO_CGROUP` (Linux 5.7), so that nothing it does happens anywhere else. Where
+    /// the kernel lacks it (ENOSYS or E2BIG) this falls back to `spawn_in`.
+    ///
+    /// `std::io::process::Command` keeps its settings to itself, so for one of those this is
+    /// always `spawn_in`; a `CgroupCommand`, configured the same way, gets the real thing.
+    fn spawn_into_cgroup(&self, cgroup: &CgroupFd) -> Result<Child> {
+        let controller = try!(Controller::at(cgroup.path().clone(), Version::V2));
+        self.spawn_in(&controller).map(Child::from_process)
+    }
+}
+
+impl CgroupCommandExt for Command {
+    fn spawn_in(&self, cgroup: &Controller) -> Result<Process> {
+        let procs = cgroup.path().join("cgroup.procs");
+        let mut child = try!(self.spawn().map_err(|e| Error::Io(procs.clone(), e)));
+        if let Err(e) = cgroup.add_pid(child.id()) {
+            let _ = child.signal_kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        Ok(child)
+    }
+}
+
+/// A command configured like a `Command`, whose settings this crate can see, so that it can be
+/// started inside a cgroup by `spawn_into_cgroup`. It keeps a `Command` with the same settings
+/// for `spawn_in` and for kernels without `CLONE_INTO_CGROUP`.
+pub struct CgroupCommand {
+    command: Command,
+    program: String,
+    args: Vec<String>,
+    /// The whole environment, once it's been changed from this process's
+    env: Option<Vec<(String, String)>>,
+    cwd: Option<Path>,
+    uid: Option<libc::uid_t>,
+    gid: Option<libc::gid_t>,
+    stdio: [StdioContainer; 3],
+}
+
+impl CgroupCommand {
+    /// A command to run `program`, looked up in `PATH`, with the settings of `Command::new`:
+    /// no arguments, this process's environment and working directory, and stdin, stdout and
+    /// stderr piped.
+    pub fn new(program: &str) -> CgroupCommand {
+        let pipes = [StdioContainer::CreatePipe(true, false),
+                     StdioContainer::CreatePipe(false, true),
+                     StdioContainer::CreatePipe(false, true)];
+        CgroupCommand {
+            command: Command::new(program),
+            program: program.to_string(),
+            args: Vec::new(),
+            env: None,
+            cwd: None,
+            uid: None,
+            gid: None,
+            stdio: pipes,
+        }
+    }
+
+    pub fn arg(&mut self, arg: &str) -> &mut CgroupCommand {
+        self.command.arg(arg);
+        self.args.push(arg.to_string());
+        self
+    }
+
+    pub fn args(&mut self, args: &[&str]) -> &mut CgroupCommand {
+        for a in args.iter() {
+            self.arg(*a);
+        }
+        self
+    }
+
+    /// Set an environment variable for the child.
+    pub fn env(&mut self, key: &str, value: &str) -> &mut CgroupCommand {
+        self.command.env(key, value);
+        let env = self.env_mut();
+        env.retain(|&(ref k, _)| k.as_slice() != key);
+        env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Leave an environment variable out of the child's environment.
+    pub fn env_remove(&mut self, key: &str) -> &mut CgroupCommand {
+        self.command.env_remove(key);
+        self.env_mut().retain(|&(ref k, _)| k.as_slice() != key);
+        self
+    }
+
+    /// Start the child with an empty environment, before any `env`.
+    pub fn env_clear(&mut self) -> &mut CgroupCommand {
+        self.command.env_set_all::<&str, &str>(&[]);
+        self.env = Some(Vec::new());
+        self
+    }
+
+    fn env_mut(&mut self) -> &mut Vec<(String, String)> {
+        if self.env.is_none() {
+            self.env = Some(os::env());
+        }
+        self.env.as_mut().unwrap()
+    }
+
+    /// The child's working directory.
+    pub fn cwd(&mut self, dir: &Path) -> &mut CgroupCommand {
+        self.command.cwd(dir);
+        self.cwd = Some(dir.clone());
+        self
+    }
+
+    /// The user the child runs as.
+    pub fn uid(&mut self, uid: libc::uid_t) -> &mut CgroupCommand {
+        self.command.uid(uid as usize);
+        self.uid = Some(uid);
+        self
+    }
+
+    /// The group the child runs as.
+    pub fn gid(&mut self, gid: libc::gid_t) -> &mut CgroupCommand {
+        self.command.gid(gid as usize);
+        self.gid = Some(gid);
+        self
+    }
+
+    pub fn stdin(&mut self, cfg: StdioContainer) -> &mut CgroupCommand {
+        self.command.stdin(cfg);
+        self.stdio[0] = cfg;
+        self
+    }
+
+    pub fn stdout(&mut self, cfg: StdioContainer) -> &mut CgroupCommand {
+        self.command.stdout(cfg);
+        self.stdio[1] = cfg;
+        self
+    }
+
+    pub fn stderr(&mut self, cfg: StdioContainer) -> &mut CgroupCommand {
+        self.command.stderr(cfg);
+        self.stdio[2] = cfg;
+        self
+    }
+
+    /// The `Command` with the same settings.
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// Fork into the cgroup and exec, or None if the kernel can't fork into a cgroup.
+    fn clone_into(&self, cgroup: &CgroupFd) -> Result<Option<Child>> {
+        let path = cgroup.path();
+        // everything the child needs is allocated here, before the fork
+        let prog = CString::from_slice(self.program.as_bytes());
+        let argv: Vec<CString> = Some(&self.program).into_iter().chain(self.args.iter())
+                                     .map(|a| CString::from_slice(a.as_bytes())).collect();
+        let mut argv_ptrs: Vec<*const c_char> = argv.iter().map(|a| a.as_ptr()).collect();
+        argv_ptrs.push(ptr::null());
+        let envp: Option<Vec<CString>> = self.env.as_ref().map(|env| {
+            env.iter().map(|&(ref k, ref v)| {
+                CString::from_slice(format!("{}={}", k, v).as_bytes())
+            }).collect()
+        });
+        let mut envp_ptrs: Vec<*const c_char> = match envp {
+            Some(ref envp) => envp.iter().map(|e| e.as_ptr()).collect(),
+            None => Vec::new(),
+        };
+        envp_ptrs.push(ptr::null());
+        let cwd = self.cwd.as_ref().map(|d| CString::from_slice(d.as_vec()));
+
+        // the child's ends of its stdio, and the parent's ends of the pipes
+        let mut child_fds: Vec<(c_int, Fd)> = Vec::new();
+        let mut pipes: Vec<Option<Fd>> = vec![None, None, None];
+        for (target, cfg) in self.stdio.iter().enumerate() {
+            match *cfg {
+                StdioContainer::Ignored => {
+                    let null = try!(Fd::open(&Path::new("/dev/null"), libc::O_RDWR));
+                    child_fds.push((target as c_int, null));
+                },
+                StdioContainer::InheritFd(fd) if fd == target as c_int => { },
+                StdioContainer::InheritFd(fd) => {
+                    let fd = unsafe { libc::dup(fd) };
+                    child_fds.push((target as c_int, try!(Fd::from_raw(fd, path))));
+                },
+                StdioContainer::CreatePipe(..) => {
+                    let (read, write) = try!(Fd::pipe(path));
+                    let (child_end, parent_end) = if target == 0 {
+                        (read, write)
+                    } else {
+                        (write, read)
+                    };
+                    child_fds.push((target as c_int, child_end));
+                    pipes[target] = Some(parent_end);
+                },
+            }
+        }
+        // the child reports a failure to exec through this, which exec closes otherwise
+        let (err_read, err_write) = try!(Fd::pipe(path));
+
+        let mut clone_args = sys::CloneArgs {
+            flags: sys::CLONE_INTO_CGROUP,
+            exit_signal: sys::SIGCHLD,
+            cgroup: cgroup.raw() as u64,
+            ..Default::default()
+        };
+        let pid = match unsafe { sys::clone3(&mut clone_args) } {
+            0 => unsafe {
+                for &(target, ref fd) in child_fds.iter() {
+                    if libc::dup2(fd.raw(), target) < 0 { exec_failed(&err_write); }
+                }
+                if let Some(gid) = self.gid {
+                    if libc::setgid(gid) < 0 { exec_failed(&err_write); }
+                }
+                if let Some(uid) = self.uid {
+                    if libc::setuid(uid) < 0 { exec_failed(&err_write); }
+                }
+                if let Some(ref cwd) = cwd {
+                    if libc::chdir(cwd.as_ptr()) < 0 { exec_failed(&err_write); }
+                }
+                if envp.is_some() {
+                    sys::environ = envp_ptrs.as_ptr();
+                }
+                sys::execvp(prog.as_ptr(), argv_ptrs.as_ptr());
+                exec_failed(&err_write)
+            },
+            pid if pid < 0 => match os::errno() as c_int {
+                libc::ENOSYS | libc::E2BIG => return Ok(None),
+                _ => return Err(Error::last_os_error(path)),
+            },
+            pid => pid as libc::pid_t,
+        };
+
+        drop(err_write);
+        drop(child_fds);
+        let mut errno = [0u8; 4];
+        if try!(err_read.read(&mut errno)) == errno.len() {
+            let mut status = 0;
+            unsafe { sys::waitpid(pid, &mut status, 0); }
+            let errno = unsafe { mem::transmute::<[u8; 4], c_int>(errno) };
+            return Err(Error::from_errno(path, errno));
+        }
+
+        let mut pipes = pipes.into_iter()
+                             .map(|p| p.and_then(|fd| PipeStream::open(fd.into_raw()).ok()));
+        Ok(Some(Child {
+            pid: pid,
+            path: path.clone(),
+            process: None,
+            stdin: pipes.next().unwrap(),
+            stdout: pipes.next().unwrap(),
+            stderr: pipes.next().unwrap(),
+        }))
+    }
+}
+
+/// In a child of `clone_into` whose setup failed: send errno to the parent and exit.
+unsafe fn exec_failed(err: &Fd) -> ! {
+    let errno = os::errno() as c_int;
+    let _ = err.write(&mem::transmute::<c_int, [u8; 4]>(errno));
+    sys::_exit(127)
+}
+
+impl CgroupCommandExt for CgroupCommand {
+    fn spawn_in(&self, cgroup: &Controller) -> Result<Process> {
+        self.command.spawn_in(cgroup)
+    }
+
+    fn spawn_into_cgroup(&self, cgroup: &CgroupFd) -> Result<Child> {
+        match try!(self.clone_into(cgroup)) {
+            Some(child) => Ok(child),
+            None => {
+                let controller = try!(Controller::at(cgroup.path().clone(), Version::V2));
+                self.spawn_in(&controller).map(Child::from_process)
+            },
+        }
+    }
+}
+
+/// A process started by `spawn_into_cgroup`, with the parent's ends of any pipes to it.
+pub struct Child {
+    pid: libc::pid_t,
+    path: Path,
+    /// The process, when it was spawned by `spawn_in`
+    process: Option<Process>,
+    pub stdin: Option<PipeStream>,
+    pub stdout: Option<PipeStream>,
+    pub stderr: Option<PipeStream>,
+}
+
+impl Child {
+    fn from_process(mut process: Process) -> Child {
+        Child {
+            pid: process.id(),
+            path: Path::new("/proc").join(process.id().to_string()),
+            stdin: process.stdin.take(),
+            stdout: process.stdout.take(),
+            stderr: process.stderr.take(),
+            process: Some(process),
+        }
+    }
+
+    /// The child's pid.
+    pub fn id(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// Wait for the child to exit.
+    pub fn wait(&mut self) -> Result<ProcessExit> {
+        if let Some(ref mut process) = self.process {
+            return process.wait().map_err(|e| Error::Io(self.path.clone(), e));
+        }
+        let mut status = 0;
+        loop {
+            if unsafe { sys::waitpid(self.pid, &mut status, 0) } >= 0 {
+                break;
+            }
+            if os::errno() as c_int != libc::EINTR {
+                return Err(Error::last_os_error(&self.path));
+            }
+        }
+        // the wait status is the exit code in the second byte, or the signal in the low bits
+        Ok(match status & 0x7f {
+            0 => ProcessExit::ExitStatus(((status >> 8) & 0xff) as isize),
+            sig => ProcessExit::ExitSignal(sig as isize),
+        })
+    }
+
+    /// Send the child SIGKILL.
+    pub fn kill(&mut self) -> Result<()> {
+        if let Some(ref mut process) = self.process {
+            return process.signal_kill().map_err(|e| Error::Io(self.path.clone(), e));
+        }
+        if unsafe { libc::funcs::posix88::signal::kill(self.pid, libc::SIGKILL) } < 0 {
+            return Err(Error::last_os_error(&self.path));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Settings named for what they do rather than for the file that holds them, written the way
+//! the hierarchy they end up in expects.
+
+use {CGroup, Controller, Limit, Result, Version};
+
+/// A setting that exists under some name in both v1 and v2.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Setting {
+    /// The hard memory limit in bytes: `memory.limit_in_bytes` or `memory.max`
+    MemoryLimit(Limit),
+    /// Memory the group keeps when the system is short: `memory.soft_limit_in_bytes` or
+    /// `memory.low`
+    MemoryReservation(Limit),
+    /// The relative weight of CPU time on the v2 scale, 1 to 10000: `cpu.shares` (converted
+    /// with `weight_to_shares`) or `cpu.weight`
+    CpuWeight(u64),
+    /// The CPU time the group may use per period in microseconds: `cpu.cfs_quota_us` or the
+    /// first field of `cpu.max`
+    CpuQuota(Limit),
+    /// The most processes and threads the group may have: `pids.max` in both
+    PidsMax(Limit),
+}
+
+impl Setting {
+    /// The controller the setting belongs to.
+    pub fn controller(&self) -> &'static [u8] {
+        match *self {
+            Setting::MemoryLimit(_) | Setting::MemoryReservation(_) => b"memory",
+            Setting::CpuWeight(_) | Setting::CpuQuota(_) => b"cpu",
+            Setting::PidsMax(_) => b"pids",
+        }
+    }
+
+    /// The file and the value to write for a hierarchy version.
+    pub fn encode(&self, version: Version) -> (&'static [u8], String) {
+        // v1 has no "max", and takes -1 instead
+        let v1 = |&: limit: Limit| match limit {
+            Limit::Max => "-1".to_string(),
+            Limit::Value(v) => v.to_string(),
+        };
+        match (*self, version) {
+            (Setting::MemoryLimit(l), Version::V1) => (b"memory.limit_in_bytes", v1(l)),
+            (Setting::MemoryLimit(l), Version::V2) => (b"memory.max", l.to_string()),
+            (Setting::MemoryReservation(l), Version::V1) => {
+                (b"memory.soft_limit_in_bytes", v1(l))
+            },
+            (Setting::MemoryReservation(l), Version::V2) => (b"memory.low", l.to_string()),
+            (Setting::CpuWeight(w), Version::V1) => {
+                (b"cpu.shares", weight_to_shares(w).to_string())
+            },
+            (Setting::CpuWeight(w), Version::V2) => (b"cpu.weight", w.to_string()),
+            (Setting::CpuQuota(l), Version::V1) => (b"cpu.cfs_quota_us", v1(l)),
+            // the period is left alone when it's not written
+            (Setting::CpuQuota(l), Version::V2) => (b"cpu.max", l.to_string()),
+            (Setting::PidsMax(l), _) => (b"pids.max", l.to_string()),
+        }
+    }
+
+    /// Write the setting to a handle for its controller.
+    pub fn write(&self, controller: &Controller) -> Result<()> {
+        let (key, value) = self.encode(controller.version());
+        controller.set(key, value.as_slice())
+    }
+}
+
+impl CGroup {
+    /// Write a setting to this process's cgroup for its controller, in whichever hierarchy
+    /// that is.
+    pub fn set_setting(&self, setting: Setting) -> Result<()> {
+        setting.write(&try!(self.controller(setting.controller())))
+    }
+}
+
+/// Map a v2 weight (1 to 10000, 100 by default) onto v1 shares (2 to 262144, 1024 by
+/// default), as systemd does, so the defaults match.
+pub fn weight_to_shares(weight: u64) -> u64 {
+    let shares = weight * 1024 / 100;
+    if shares < 2 { 2 } else if shares > 262144 { 262144 } else { shares }
+}
+
+/// Map v1 shares onto a v2 weight, the inverse of `weight_to_shares`.
+pub fn shares_to_weight(shares: u64) -> u64 {
+    let weight = shares * 100 / 1024;
+    if weight < 1 { 1 } else if weight > 10000 { 10000 } else { weight }
+}
@@ -0,0 +1,152 @@
+//! Applying limits described in a TOML or JSON file.
+//!
+//! A config has a table per controller, holding the control files to write:
+//!
+//! ```toml
+//! [memory]
+//! "memory.max" = "512M"
+//! "memory.swap.max" = 0
+//!
+//! [pids]
+//! "pids.max" = 100
+//! ```
+//!
+//! Numbers are written as they are, booleans as `1` or `0`.
+
+use std::collections::BTreeMap;
+use rustc_serialize::json::Json;
+use toml;
+
+use {CGroup, Result};
+
+/// Values to write, by controller name and then control file.
+#[derive(Clone, PartialEq, Show, Default)]
+pub struct Config {
+    pub controllers: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// The outcome of writing one value.
+#[derive(Show)]
+pub struct KeyResult {
+    pub controller: String,
+    pub key: String,
+    pub result: Result<()>,
+}
+
+/// What happened to every value in a config, in the order they were written.
+#[derive(Show)]
+pub struct Report {
+    pub results: Vec<KeyResult>,
+}
+
+impl Report {
+    /// Whether every value was written.
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(|r| r.result.is_ok())
+    }
+
+    /// The values that couldn't be written.
+    pub fn failures(&self) -> Vec<&KeyResult> {
+        self.results.iter().filter(|r| r.result.is_err()).collect()
+    }
+}
+
+impl Config {
+    /// Read a config file, as JSON if its name ends in `.json` and as TOML otherwise.
+    pub fn load(path: &Path) -> Result<Config> {
+        let contents = try!(::read_file(path));
+        let parsed = if path.extension_str() == Some("json") {
+            Config::from_json(contents.as_slice())
+        } else {
+            Config::from_toml(contents.as_slice())
+        };
+        parsed.map_err(|e| ::Error::InvalidValue(path.clone(), e))
+    }
+
+    /// Parse a TOML config.
+    pub fn from_toml(s: &str) -> ::std::result::Result<Config, String> {
+        let mut parser = toml::Parser::new(s);
+        let table = match parser.parse() {
+            Some(t) => t,
+            None => {
+                let errors: Vec<String> = parser.errors.iter().map(|e| e.desc.clone()).collect();
+                return Err(errors.connect("; "));
+            },
+        };
+
+        let mut config = Config::default();
+        for (controller, value) in table.into_iter() {
+            let keys = match value {
+                toml::Value::Table(keys) => keys,
+                _ => return Err(format!("{} is not a table", controller)),
+            };
+            let mut values = BTreeMap::new();
+            for (key, value) in keys.into_iter() {
+                let value = match value {
+                    toml::Value::String(s) => s,
+                    toml::Value::Integer(n) => n.to_string(),
+                    toml::Value::Float(f) => f.to_string(),
+                    toml::Value::Boolean(b) => if b { "1" } else { "0" }.to_string(),
+                    _ => return Err(format!("{}.{} is not a string, number or boolean",
+                                            controller, key)),
+                };
+                values.insert(key, value);
+            }
+            config.controllers.insert(controller, values);
+        }
+        Ok(config)
+    }
+
+    /// Parse a JSON config, an object of objects laid out like the TOML tables.
+    pub fn from_json(s: &str) -> ::std::result::Result<Config, String> {
+        let controllers = match Json::from_str(s) {
+            Ok(Json::Object(o)) => o,
+            Ok(_) => return Err("not an object".to_string()),
+            Err(e) => return Err(format!("{:?}", e)),
+        };
+
+        let mut config = Config::default();
+        for (controller, value) in controllers.into_iter() {
+            let keys = match value {
+                Json::Object(keys) => keys,
+                _ => return Err(format!("{} is not an object", controller)),
+            };
+            let mut values = BTreeMap::new();
+            for (key, value) in keys.into_iter() {
+                let value = match value {
+                    Json::String(s) => s,
+                    Json::I64(n) => n.to_string(),
+                    Json::U64(n) => n.to_string(),
+                    Json::F64(f) => f.to_string(),
+                    Json::Boolean(b) => if b { "1" } else { "0" }.to_string(),
+                    _ => return Err(format!("{}.{} is not a string, number or boolean",
+                                            controller, key)),
+                };
+                values.insert(key, value);
+            }
+            config.controllers.insert(controller, values);
+        }
+        Ok(config)
+    }
+
+    /// Write every value to the cgroup, carrying on past failures. A controller that isn't
+    /// available fails each of its keys.
+    pub fn apply(&self, cg: &CGroup) -> Report {
+        let mut results = Vec::new();
+        for (controller, values) in self.controllers.iter() {
+            let cont = cg.controller(controller.as_bytes());
+            for (key, value) in values.iter() {
+                let result = match cont {
+                    Ok(ref c) => c.set(key.as_bytes(), value.as_slice()),
+                    Err(ref e) => Err(e.clone()),
+                };
+                results.push(KeyResult {
+                    controller: controller.clone(),
+                    key: key.clone(),
+                    result: result,
+                });
+            }
+        }
+        Report { results: results }
+    }
+}
@@ -0,0 +1,230 @@
+//! Telling which container a cgroup belongs to from its path.
+//!
+//! Container runtimes name cgroups after the container's id, in a handful of shapes depending
+//! on the runtime and on whether it drives cgroups directly (`/docker/<id>`) or through systemd
+//! (`/system.slice/docker-<id>.scope`). Kubernetes adds the pod and its QoS class above that,
+//! as in `/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod<uid>.slice/...`.
+//! containerd with the systemd driver can also leave the driver's own notation in the path,
+//! `kubepods-burstable-pod<uid>.slice:cri-containerd:<id>`, in place of the scope.
+
+/// The runtime that created a container's cgroup.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Runtime {
+    Docker,
+    Containerd,
+    CriO,
+    Podman,
+    /// A bare container id, as Kubernetes' cgroupfs driver uses for any runtime
+    Unknown,
+}
+
+/// The QoS class of a Kubernetes pod.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum QosClass {
+    Guaranteed,
+    Burstable,
+    BestEffort,
+}
+
+/// Who a container's cgroup belongs to.
+#[derive(Clone, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ContainerId {
+    pub runtime: Runtime,
+    /// The container's id, in hex
+    pub id: String,
+    /// The uid of the Kubernetes pod the container is in, if any
+    pub pod_uid: Option<String>,
+    /// The QoS class of that pod
+    pub qos: Option<QosClass>,
+}
+
+/// Runtime prefixes of systemd scope names, `<prefix><id>.scope`. The monitor processes of
+/// CRI-O and podman get scopes of their own, which don't count.
+static SCOPE_PREFIXES: &'static [(&'static str, Runtime)] = &[
+    ("docker-", Runtime::Docker),
+    ("cri-containerd-", Runtime::Containerd),
+    ("crio-", Runtime::CriO),
+    ("libpod-", Runtime::Podman),
+];
+
+fn is_id(s: &str) -> bool {
+    s.len() >= 12 && s.chars().all(|c| c.is_digit(16))
+}
+
+/// The pod uid in a component like `pod<uid>` or `kubepods-burstable-pod<uid>.slice`, where
+/// systemd's naming turns the dashes of the uid into underscores.
+fn pod_uid(component: &str) -> Option<String> {
+    let rest = if component.starts_with("pod") {
+        &component[3..]
+    } else if component.starts_with("kubepods-") && component.ends_with(".slice") {
+        match component.find_str("-pod") {
+            Some(i) => &component[i + 4..component.len() - ".slice".len()],
+            None => return None,
+        }
+    } else {
+        return None;
+    };
+    if rest.is_empty() {
+        return None;
+    }
+    Some(rest.replace("_", "-"))
+}
+
+/// Find the innermost container in a cgroup path, None if it doesn't look like one.
+pub fn parse(path: &str) -> Option<ContainerId> {
+    let mut found: Option<(Runtime, String)> = None;
+    let mut kubepods = false;
+    let mut pod = None;
+    let mut qos = None;
+    let mut parent = "";
+
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        // `<slice>:<prefix>:<id>`, the slice being the pod's
+        let mut pieces = component.splitn(2, ':');
+        let (component, colon) = match (pieces.next(), pieces.next(), pieces.next()) {
+            (Some(slice), Some(prefix), Some(id)) => (slice, Some((prefix, id))),
+            _ => (component, None),
+        };
+
+        if component == "kubepods" || component.starts_with("kubepods.") ||
+           component.starts_with("kubepods-") {
+            kubepods = true;
+        }
+        if kubepods {
+            if component == "burstable" || component.starts_with("kubepods-burstable") {
+                qos = Some(QosClass::Burstable);
+            } else if component == "besteffort" || component.starts_with("kubepods-besteffort") {
+                qos = Some(QosClass::BestEffort);
+            }
+            if let Some(uid) = pod_uid(component) {
+                pod = Some(uid);
+            }
+        }
+
+        let scope = if component.ends_with(".scope") {
+            &component[..component.len() - ".scope".len()]
+        } else {
+            component
+        };
+        let mut matched = false;
+        for &(prefix, runtime) in SCOPE_PREFIXES.iter() {
+            if let Some((name, id)) = colon {
+                // the prefix without its dash
+                if name == &prefix[..prefix.len() - 1] && is_id(id) {
+                    found = Some((runtime, id.to_string()));
+                    matched = true;
+                }
+            } else if scope.starts_with(prefix) && is_id(&scope[prefix.len()..]) {
+                found = Some((runtime, scope[prefix.len()..].to_string()));
+                matched = true;
+            }
+        }
+        if !matched && is_id(component) {
+            let runtime = match parent {
+                "docker" => Runtime::Docker,
+                _ => Runtime::Unknown,
+            };
+            found = Some((runtime, component.to_string()));
+        }
+        parent = component;
+    }
+
+    found.map(|(runtime, id)| ContainerId {
+        runtime: runtime,
+        id: id,
+        // pods without a QoS level of their own are guaranteed
+        qos: if pod.is_some() { Some(qos.unwrap_or(QosClass::Guaranteed)) } else { None },
+        pod_uid: pod,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, ContainerId, QosClass, Runtime};
+
+    static ID: &'static str = "4d5c3b2a1f0e9d8c7b6a5f4e3d2c1b0a4d5c3b2a1f0e9d8c7b6a5f4e3d2c1b0a";
+
+    fn plain(runtime: Runtime) -> Option<ContainerId> {
+        Some(ContainerId { runtime: runtime, id: ID.to_string(), pod_uid: None, qos: None })
+    }
+
+    fn in_pod(runtime: Runtime, uid: &str, qos: QosClass) -> Option<ContainerId> {
+        Some(ContainerId {
+            runtime: runtime,
+            id: ID.to_string(),
+            pod_uid: Some(uid.to_string()),
+            qos: Some(qos),
+        })
+    }
+
+    #[test]
+    fn docker() {
+        assert_eq!(parse(format!("/docker/{}", ID).as_slice()), plain(Runtime::Docker));
+        assert_eq!(parse(format!("/system.slice/docker-{}.scope", ID).as_slice()),
+                   plain(Runtime::Docker));
+        // short ids count too
+        assert_eq!(parse("/docker/4d5c3b2a1f0e").map(|c| c.id),
+                   Some("4d5c3b2a1f0e".to_string()));
+    }
+
+    #[test]
+    fn containerd() {
+        let path = format!("/kubepods.slice/kubepods-burstable.slice/\
+                            kubepods-burstable-pod6b9e2c1a_3f4d_4e5a.slice/cri-containerd-{}.scope",
+                           ID);
+        assert_eq!(parse(path.as_slice()),
+                   in_pod(Runtime::Containerd, "6b9e2c1a-3f4d-4e5a", QosClass::Burstable));
+    }
+
+    #[test]
+    fn containerd_colon() {
+        let path = format!("/kubepods.slice/kubepods-besteffort.slice/\
+                            kubepods-besteffort-pod6b9e2c1a_3f4d.slice:cri-containerd:{}", ID);
+        assert_eq!(parse(path.as_slice()),
+                   in_pod(Runtime::Containerd, "6b9e2c1a-3f4d", QosClass::BestEffort));
+        // not a runtime this knows
+        let path = format!("/kubepods.slice/kubepods-pod6b9e.slice:other:{}", ID);
+        assert_eq!(parse(path.as_slice()), None);
+    }
+
+    #[test]
+    fn crio() {
+        let path = format!("/kubepods.slice/kubepods-pod6b9e2c1a_3f4d.slice/crio-{}.scope", ID);
+        assert_eq!(parse(path.as_slice()),
+                   in_pod(Runtime::CriO, "6b9e2c1a-3f4d", QosClass::Guaranteed));
+        // the monitor's scope isn't the container
+        let path = format!("/kubepods.slice/kubepods-pod6b9e.slice/crio-conmon-{}.scope", ID);
+        assert_eq!(parse(path.as_slice()), None);
+    }
+
+    #[test]
+    fn podman() {
+        assert_eq!(parse(format!("/machine.slice/libpod-{}.scope", ID).as_slice()),
+                   plain(Runtime::Podman));
+        // rootless, with the container's own cgroup beneath the scope
+        let path = format!("/user.slice/user-1000.slice/user@1000.service/user.slice/\
+                            libpod-{}.scope/container", ID);
+        assert_eq!(parse(path.as_slice()), plain(Runtime::Podman));
+    }
+
+    #[test]
+    fn kubepods_cgroupfs() {
+        let path = format!("/kubepods/besteffort/pod6b9e2c1a-3f4d-4e5a/{}", ID);
+        assert_eq!(parse(path.as_slice()),
+                   in_pod(Runtime::Unknown, "6b9e2c1a-3f4d-4e5a", QosClass::BestEffort));
+        let path = format!("/kubepods/pod6b9e2c1a-3f4d/{}", ID);
+        assert_eq!(parse(path.as_slice()),
+                   in_pod(Runtime::Unknown, "6b9e2c1a-3f4d", QosClass::Guaranteed));
+    }
+
+    #[test]
+    fn not_containers() {
+        assert_eq!(parse("/"), None);
+        assert_eq!(parse("/user.slice/user-1000.slice/session-2.scope"), None);
+        assert_eq!(parse("/system.slice/docker.service"), None);
+        assert_eq!(parse("/system.slice/docker-abc.scope"), None);
+    }
+}
@@ -0,0 +1,390 @@
+//! The cpu controller.
+
+use time;
+
+use {Controller, Error, Limit, Result, Version};
+use sys;
+
+/// The kernel's default enforcement period, in microseconds.
+const DEFAULT_PERIOD: u64 = 100000;
+/// The shortest quota and the longest period the kernel takes, in microseconds.
+const MIN_QUOTA: u64 = 1000;
+const MAX_PERIOD: u64 = 1000000;
+
+flat_keyed! {
+    /// Counters from `cpu.stat`.
+    ///
+    /// v1 reports throttled time in nanoseconds as `throttled_time`, v2 in microseconds as
+    /// `throttled_usec`; only the one for this controller's version is filled in. Usage is only
+    /// in the v2 file, and the burst counters in either from 5.14 on.
+    pub struct CpuStat {
+        /// Total CPU time used, in microseconds (v2)
+        usage_usec,
+        /// CPU time used in user mode, in microseconds (v2)
+        user_usec,
+        /// CPU time used in the kernel, in microseconds (v2)
+        system_usec,
+        /// Enforcement periods that have elapsed
+        nr_periods,
+        /// Periods in which the group was throttled
+        nr_throttled,
+        /// Total time throttled, in nanoseconds (v1)
+        throttled_time,
+        /// Total time throttled, in microseconds (v2)
+        throttled_usec,
+        /// Periods in which the group used some of its burst
+        nr_bursts,
+        /// Total time run on burst past the quota, in microseconds (v2)
+        burst_usec,
+        /// Total time run on burst past the quota, in nanoseconds (v1)
+        burst_time
+    }
+}
+
+impl CpuStat {
+    /// Total time throttled in nanoseconds, whichever version it came from.
+    pub fn throttled_ns(&self) -> u64 {
+        self.throttled_time + self.throttled_usec * 1000
+    }
+
+    /// The share of periods in which the group was throttled, from 0 to 1.
+    pub fn throttled_ratio(&self) -> f64 {
+        if self.nr_periods == 0 {
+            0.0
+        } else {
+            self.nr_throttled as f64 / self.nr_periods as f64
+        }
+    }
+}
+
+/// A reading of the CPU time a group has used, with when it was taken.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CpuUsage {
+    /// Total CPU time used, in nanoseconds
+    pub usage_ns: u64,
+    /// Monotonic clock in nanoseconds at the time of reading
+    pub monotonic_ns: u64,
+}
+
+/// CPU time a group has used, split by where it was spent.
+#[derive(Clone, Copy, PartialEq, Eq, Show, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CpuTimes {
+    /// Time in user mode, in nanoseconds
+    pub user_ns: u64,
+    /// Time in the kernel, in nanoseconds
+    pub system_ns: u64,
+}
+
+/// How busy a group kept the CPUs between two readings.
+#[derive(Clone, Copy, PartialEq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Utilization {
+    /// CPU time used per wall-clock time, so 1.0 is one core fully busy
+    pub cores: f64,
+    /// How much of the quota was used, 1.0 being all of it; None without a quota
+    pub of_quota: Option<f64>,
+}
+
+impl CpuUsage {
+    /// The cores kept busy on average since an earlier reading.
+    pub fn cores_since(&self, earlier: &CpuUsage) -> f64 {
+        if self.monotonic_ns <= earlier.monotonic_ns || self.usage_ns < earlier.usage_ns {
+            return 0.0;
+        }
+        (self.usage_ns - earlier.usage_ns) as f64 /
+            (self.monotonic_ns - earlier.monotonic_ns) as f64
+    }
+}
+
+/// Typed access to the cpu controller, hiding the file differences between v1 and v2.
+pub struct CpuController {
+    inner: Controller,
+}
+
+impl CpuController {
+    /// Wrap a controller handle for the cpu controller.
+    pub fn new(inner: Controller) -> CpuController {
+        CpuController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// The relative share of CPU time, from the v1 `cpu.shares`.
+    pub fn shares(&self) -> Result<u64> {
+        self.inner.get_u64(b"cpu.shares")
+    }
+
+    /// Set the v1 `cpu.shares`.
+    pub fn set_shares(&self, shares: u64) -> Result<()> {
+        self.inner.set(b"cpu.shares", format!("{}", shares).as_slice())
+    }
+
+    /// The relative weight of CPU time, from the v2 `cpu.weight`.
+    pub fn weight(&self) -> Result<u64> {
+        self.inner.get_u64(b"cpu.weight")
+    }
+
+    /// Set the v2 `cpu.weight`, between 1 and 10000.
+    pub fn set_weight(&self, weight: u64) -> Result<()> {
+        self.inner.set(b"cpu.weight", format!("{}", weight).as_slice())
+    }
+
+    /// Read `cpu.max` as (quota, period).
+    fn max(&self) -> Result<(Limit, u64)> {
+        let s = try!(self.inner.get(b"cpu.max"));
+        let words: Vec<&str> = s.as_slice().words().collect();
+        if words.len() == 2 {
+            if let (Some(quota), Some(period)) = (Limit::parse(words[0]), words[1].parse()) {
+                return Ok((quota, period));
+            }
+        }
+        Err(self.inner.invalid_value(b"cpu.max", s.as_slice()))
+    }
+
+    /// The CPU time the group may use per period, in microseconds.
+    pub fn quota(&self) -> Result<Limit> {
+        match self.inner.version() {
+            Version::V1 => {
+                let q = try!(self.inner.get_i64(b"cpu.cfs_quota_us"));
+                Ok(if q < 0 { Limit::Max } else { Limit::Value(q as u64) })
+            },
+            Version::V2 => self.max().map(|(q, _)| q),
+        }
+    }
+
+    /// The most CPU time the group can get, in cores: the smallest quota per period of the
+    /// group and its ancestors, None if none of them has one.
+    pub fn effective_max(&self) -> Result<Option<f64>> {
+        let mut max = None;
+        let mut levels = vec![try!(self.inner.reopen())];
+        levels.extend(try!(self.inner.ancestors()).into_iter());
+        for level in levels.into_iter() {
+            let cpu = CpuController::new(level);
+            let cores = match cpu.quota() {
+                Ok(Limit::Value(quota)) => quota as f64 / try!(cpu.period()) as f64,
+                // the v2 root has no cpu.max
+                Ok(Limit::Max) | Err(Error::KeyNotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            if max.map_or(true, |m| cores < m) {
+                max = Some(cores);
+            }
+        }
+        Ok(max)
+    }
+
+    /// The length of an enforcement period, in microseconds.
+    pub fn period(&self) -> Result<u64> {
+        match self.inner.version() {
+            Version::V1 => self.inner.get_u64(b"cpu.cfs_period_us"),
+            Version::V2 => self.max().map(|(_, p)| p),
+        }
+    }
+
+    /// Set the CPU time the group may use per period, in microseconds.
+    pub fn set_quota(&self, quota: Limit) -> Result<()> {
+        match self.inner.version() {
+            Version::V1 => self.inner.set(b"cpu.cfs_quota_us", quota.to_v1_string().as_slice()),
+            // the period is optional and left alone when it's not written
+            Version::V2 => self.inner.set(b"cpu.max", quota.to_string().as_slice()),
+        }
+    }
+
+    /// Set the length of an enforcement period, in microseconds.
+    pub fn set_period(&self, period: u64) -> Result<()> {
+        match self.inner.version() {
+            Version::V1 => self.inner.set(b"cpu.cfs_period_us", format!("{}", period).as_slice()),
+            Version::V2 => {
+                let (quota, _) = try!(self.max());
+                let value = format!("{} {}", quota, period);
+                self.inner.set(b"cpu.max", value.as_slice())
+            },
+        }
+    }
+
+    /// How far past its quota the group may run in a period, on time left unused in earlier
+    /// periods, in microseconds; from the v1 `cpu.cfs_burst_us` or the v2 `cpu.max.burst`
+    /// (5.14 and later).
+    pub fn burst(&self) -> Result<u64> {
+        self.inner.get_u64(self.burst_key())
+    }
+
+    /// Set the burst, which can't be more than the quota, in microseconds; 0 turns it off. A
+    /// burst over the current quota is `InvalidValue`, so lower the burst before the quota.
+    pub fn set_burst(&self, burst: u64) -> Result<()> {
+        let key = self.burst_key();
+        if let Limit::Value(quota) = try!(self.quota()) {
+            if burst > quota {
+                return Err(Error::InvalidValue(self.inner.path().join(key), format!(
+                    "burst {}us is over the quota of {}us", burst, quota)));
+            }
+        }
+        self.inner.set(key, burst.to_string().as_slice())
+    }
+
+    fn burst_key(&self) -> &'static [u8] {
+        match self.inner.version() {
+            Version::V1 => b"cpu.cfs_burst_us",
+            Version::V2 => b"cpu.max.burst",
+        }
+    }
+
+    /// Limit the group to a number of cores' worth of CPU time, like 1.5.
+    ///
+    /// The period is the kernel's default of 100ms, made longer (up to a second) for limits
+    /// under a hundredth of a core, since quotas can't be shorter than 1ms; smaller limits are
+    /// `InvalidValue`, as are limits that aren't positive. A longer period lets the group use its
+    /// quota in bigger bursts.
+    pub fn set_limit_cores(&self, cores: f64) -> Result<()> {
+        let mut period = DEFAULT_PERIOD;
+        if cores > 0.0 && cores * (period as f64) < MIN_QUOTA as f64 {
+            period = (MIN_QUOTA as f64 / cores) as u64 + 1;
+        }
+        let quota = (cores * period as f64) as u64;
+        if !(cores > 0.0) || period > MAX_PERIOD || quota < MIN_QUOTA {
+            let key = match self.inner.version() {
+                Version::V1 => "cpu.cfs_quota_us",
+                Version::V2 => "cpu.max",
+            };
+            return Err(Error::InvalidValue(self.inner.path().join(key),
+                                           format!("can't limit to {} cores", cores)));
+        }
+
+        match self.inner.version() {
+            Version::V1 => {
+                // drop the old quota first, so it's never checked against the new period
+                try!(self.set_quota(Limit::Max));
+                try!(self.set_period(period));
+                self.set_quota(Limit::Value(quota))
+            },
+            Version::V2 => self.inner.set(b"cpu.max", format!("{} {}", quota, period).as_slice()),
+        }
+    }
+
+    /// Whether the group's tasks run as SCHED_IDLE, only when nothing else wants the CPU, from
+    /// the v2 `cpu.idle` (5.15 and later).
+    pub fn idle(&self) -> Result<bool> {
+        self.inner.get_bool(b"cpu.idle")
+    }
+
+    /// Mark the group idle-priority, for background work, or give it back its weight.
+    pub fn set_idle(&self, idle: bool) -> Result<()> {
+        self.inner.set(b"cpu.idle", if idle { "1" } else { "0" })
+    }
+
+    /// The minimum utilization the scheduler assumes for the group's tasks, as a percentage,
+    /// from `cpu.uclamp.min`.
+    pub fn uclamp_min(&self) -> Result<f64> {
+        self.uclamp(b"cpu.uclamp.min")
+    }
+
+    /// The maximum utilization the scheduler assumes for the group's tasks, as a percentage,
+    /// from `cpu.uclamp.max`.
+    pub fn uclamp_max(&self) -> Result<f64> {
+        self.uclamp(b"cpu.uclamp.max")
+    }
+
+    /// Set `cpu.uclamp.min`, a percentage from 0 to 100 with up to two decimals.
+    pub fn set_uclamp_min(&self, percent: f64) -> Result<()> {
+        self.set_uclamp(b"cpu.uclamp.min", percent)
+    }
+
+    /// Set `cpu.uclamp.max`, a percentage from 0 to 100 with up to two decimals.
+    pub fn set_uclamp_max(&self, percent: f64) -> Result<()> {
+        self.set_uclamp(b"cpu.uclamp.max", percent)
+    }
+
+    /// Read a uclamp file, which holds a percentage like `12.34`, or `max` for 100.
+    fn uclamp(&self, key: &[u8]) -> Result<f64> {
+        let s = try!(self.inner.get(key));
+        match s.as_slice().trim() {
+            "max" => Ok(100.0),
+            v => match v.parse() {
+                Some(p) => Ok(p),
+                None => Err(self.inner.invalid_value(key, s.as_slice())),
+            },
+        }
+    }
+
+    fn set_uclamp(&self, key: &[u8], percent: f64) -> Result<()> {
+        if !(percent >= 0.0 && percent <= 100.0) {
+            return Err(Error::InvalidValue(self.inner.path().join(key),
+                                           format!("{} is not a percentage", percent)));
+        }
+        let value = if percent == 100.0 { "max".to_string() } else { format!("{:.2}", percent) };
+        self.inner.set(key, value.as_slice())
+    }
+
+    /// Read the CPU time used so far, from the v1 `cpuacct.usage` (in nanoseconds, when cpuacct
+    /// is mounted together with cpu) or the `usage_usec` of the v2 `cpu.stat`.
+    pub fn usage(&self) -> Result<CpuUsage> {
+        let usage_ns = match self.inner.version() {
+            Version::V1 => try!(self.inner.get_u64(b"cpuacct.usage")),
+            Version::V2 => {
+                let stat = try!(self.inner.get_flat_keyed(b"cpu.stat"));
+                match stat.get("usage_usec") {
+                    Some(&us) => us * 1000,
+                    None => return Err(self.inner.invalid_value(b"cpu.stat", "no usage_usec")),
+                }
+            },
+        };
+        Ok(CpuUsage { usage_ns: usage_ns, monotonic_ns: time::precise_time_ns() })
+    }
+
+    /// CPU time used so far on each CPU, in nanoseconds, from the v1
+    /// `cpuacct.usage_percpu`. v2 only counts usage for all CPUs together.
+    pub fn usage_per_cpu(&self) -> Result<Vec<u64>> {
+        let key = b"cpuacct.usage_percpu";
+        try!(self.inner.require_version(Version::V1, key));
+        let s = try!(self.inner.get(key));
+        let usage: Option<Vec<u64>> = s.as_slice().words().map(|w| w.parse()).collect();
+        match usage {
+            Some(u) => Ok(u),
+            None => Err(self.inner.invalid_value(key, s.as_slice())),
+        }
+    }
+
+    /// CPU time used so far in user mode and in the kernel, from the v1 `cpuacct.stat` (kept
+    /// in clock ticks, so only that precise) or the `user_usec` and `system_usec` of the v2
+    /// `cpu.stat`.
+    pub fn times(&self) -> Result<CpuTimes> {
+        let (key, scale): (&[u8], u64) = match self.inner.version() {
+            Version::V1 => (b"cpuacct.stat", 1000000000 / sys::clock_ticks()),
+            Version::V2 => (b"cpu.stat", 1000),
+        };
+        let stat = try!(self.inner.get_flat_keyed(key));
+        let (user, system) = match self.inner.version() {
+            Version::V1 => (stat.get("user"), stat.get("system")),
+            Version::V2 => (stat.get("user_usec"), stat.get("system_usec")),
+        };
+        match (user, system) {
+            (Some(&user), Some(&system)) => Ok(CpuTimes {
+                user_ns: user * scale,
+                system_ns: system * scale,
+            }),
+            _ => Err(self.inner.invalid_value(key, "no user and system times")),
+        }
+    }
+
+    /// The utilization between two readings from `usage`, relative both to one core and to the
+    /// group's current quota.
+    pub fn utilization(&self, earlier: &CpuUsage, later: &CpuUsage) -> Result<Utilization> {
+        let cores = later.cores_since(earlier);
+        let of_quota = match try!(self.quota()) {
+            // the quota and period are both in microseconds, so this is in cores too
+            Limit::Value(quota) => Some(cores / (quota as f64 / try!(self.period()) as f64)),
+            Limit::Max => None,
+        };
+        Ok(Utilization { cores: cores, of_quota: of_quota })
+    }
+
+    /// The counters from `cpu.stat`.
+    pub fn stat(&self) -> Result<CpuStat> {
+        self.inner.get_flat_keyed_as(b"cpu.stat")
+    }
+}
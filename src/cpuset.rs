@@ -0,0 +1,124 @@
+//! The cpuset controller.
+
+use std::collections::BTreeSet;
+
+use {Controller, Result, Version};
+
+/// Parse the kernel's list syntax, like `0-3,5,7-9`, into a set. None if it is malformed.
+pub fn parse_list(s: &str) -> Option<BTreeSet<usize>> {
+    let mut set = BTreeSet::new();
+    let s = s.trim();
+    if s.is_empty() {
+        return Some(set);
+    }
+    for item in s.split(',') {
+        let mut bounds = item.splitn(1, '-');
+        let start: usize = match bounds.next().and_then(|b| b.parse()) {
+            Some(b) => b,
+            None => return None,
+        };
+        let end: usize = match bounds.next() {
+            Some(b) => match b.parse() {
+                Some(b) => b,
+                None => return None,
+            },
+            None => start,
+        };
+        if end < start {
+            return None;
+        }
+        for i in range(start, end + 1) {
+            set.insert(i);
+        }
+    }
+    Some(set)
+}
+
+/// Format a set of CPUs or nodes in the kernel's list syntax, collapsing runs into ranges.
+pub fn format_list<'a, I: Iterator<Item=&'a usize>>(items: I) -> String {
+    let set: BTreeSet<usize> = items.map(|&i| i).collect();
+    let mut out = String::new();
+    let mut iter = set.iter().map(|&i| i).peekable();
+    loop {
+        let start = match iter.next() {
+            Some(i) => i,
+            None => break,
+        };
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end = iter.next().unwrap();
+        }
+        if !out.is_empty() {
+            out.push(',');
+        }
+        if start == end {
+            out.push_str(format!("{}", start).as_slice());
+        } else {
+            out.push_str(format!("{}-{}", start, end).as_slice());
+        }
+    }
+    out
+}
+
+/// Typed access to the cpuset controller.
+pub struct CpusetController {
+    inner: Controller,
+}
+
+impl CpusetController {
+    /// Wrap a controller handle for the cpuset controller.
+    pub fn new(inner: Controller) -> CpusetController {
+        CpusetController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    fn get_list(&self, key: &[u8]) -> Result<BTreeSet<usize>> {
+        let s = try!(self.inner.get(key));
+        match parse_list(s.as_slice()) {
+            Some(set) => Ok(set),
+            None => Err(self.inner.invalid_value(key, s.as_slice())),
+        }
+    }
+
+    /// The CPUs the group is configured to run on.
+    ///
+    /// On v2 an empty set means the group inherits its parent's CPUs.
+    pub fn cpus(&self) -> Result<BTreeSet<usize>> {
+        self.get_list(b"cpuset.cpus")
+    }
+
+    /// The memory nodes the group is configured to allocate from.
+    pub fn mems(&self) -> Result<BTreeSet<usize>> {
+        self.get_list(b"cpuset.mems")
+    }
+
+    /// The CPUs the group can actually run on, after the parent's restrictions and hotplug.
+    pub fn effective_cpus(&self) -> Result<BTreeSet<usize>> {
+        match self.inner.version() {
+            Version::V1 => self.get_list(b"cpuset.effective_cpus"),
+            Version::V2 => self.get_list(b"cpuset.cpus.effective"),
+        }
+    }
+
+    /// The memory nodes the group can actually allocate from.
+    pub fn effective_mems(&self) -> Result<BTreeSet<usize>> {
+        match self.inner.version() {
+            Version::V1 => self.get_list(b"cpuset.effective_mems"),
+            Version::V2 => self.get_list(b"cpuset.mems.effective"),
+        }
+    }
+
+    /// Set the CPUs the group may run on.
+    pub fn set_cpus(&self, cpus: &[usize]) -> Result<()> {
+        self.inner.set(b"cpuset.cpus", format_list(cpus.iter()).as_slice())
+    }
+
+    /// Set the memory nodes the group may allocate from.
+    pub fn set_mems(&self, mems: &[usize]) -> Result<()> {
+        self.inner.set(b"cpuset.mems", format_list(mems.iter()).as_slice())
+    }
+}
@@ -0,0 +1,108 @@
+//! Telling which hierarchies are in use.
+
+use std::io::fs;
+use libc;
+
+use {CGroup, ControllerSet, Error, Result, Version};
+
+/// The inode of the initial cgroup namespace, the one every process is in unless it (or an
+/// ancestor) unshared a new one.
+const INIT_CGROUP_NS: u64 = 0xEFFFFFFB;
+
+/// How the cgroup hierarchies of a system are set up.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Layout {
+    /// Only v1 hierarchies
+    Legacy,
+    /// Only the unified hierarchy
+    Unified,
+    /// v1 hierarchies for the controllers alongside a unified hierarchy, as systemd sets up in
+    /// its hybrid mode. Each controller is bound to one or the other.
+    Hybrid,
+}
+
+impl CGroup {
+    /// The names of the controllers of this process's v1 hierarchies.
+    fn v1_controller_names(&self) -> Vec<Vec<u8>> {
+        let mut names = Vec::new();
+        for hierarchy in self.controllers.keys() {
+            for name in hierarchy.as_slice().split(|&b| b == b',') {
+                if !name.is_empty() && !name.starts_with(b"name=") {
+                    names.push(name.to_vec());
+                }
+            }
+        }
+        names
+    }
+
+    /// The controllers that share a v1 hierarchy with `controller`, itself included, as the
+    /// kernel lists them; None if it isn't in one of this process's v1 hierarchies.
+    pub fn comounted(&self, controller: &[u8]) -> Option<Vec<Vec<u8>>> {
+        self.hierarchy_key(controller).map(|h| {
+            h.split(|&b| b == b',').filter(|n| !n.starts_with(b"name=")).map(|n| n.to_vec())
+             .collect()
+        })
+    }
+
+    /// How the hierarchies this process is in are set up.
+    pub fn layout(&self) -> Layout {
+        match (!self.v1_controller_names().is_empty(), self.unified.is_some()) {
+            (true, true) => Layout::Hybrid,
+            (false, true) => Layout::Unified,
+            _ => Layout::Legacy,
+        }
+    }
+
+    /// Whether the unified hierarchy is mounted with `nsdelegate`; see `Mount::nsdelegate`.
+    /// False when its mount wasn't found, as with `from_base_and_pid`.
+    pub fn nsdelegate(&self) -> bool {
+        self.unified_options.iter().any(|o| o.as_slice() == "nsdelegate")
+    }
+
+    /// Whether the unified hierarchy is mounted with `memory_recursiveprot`; see
+    /// `Mount::memory_recursiveprot`. False when its mount wasn't found.
+    pub fn memory_recursiveprot(&self) -> bool {
+        self.unified_options.iter().any(|o| o.as_slice() == "memory_recursiveprot")
+    }
+
+    /// Which hierarchy owns a controller, None if neither has it.
+    ///
+    /// A controller bound to a v1 hierarchy can't be used in the unified one, so this checks v1
+    /// first, then the root `cgroup.controllers` of the unified hierarchy.
+    pub fn version_of(&self, controller: &[u8]) -> Result<Option<Version>> {
+        if self.v1_controller_names().iter().any(|n| n.as_slice() == controller) {
+            return Ok(Some(Version::V1));
+        }
+        if self.unified.is_none() {
+            return Ok(None);
+        }
+        let root = try!(::read_file(&self.unified_base().join("cgroup.controllers")));
+        if ControllerSet::parse(root.as_slice()).contains(controller) {
+            Ok(Some(Version::V2))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// The cgroup namespace a process is in, as the inode number `/proc/<pid>/ns/cgroup` links to.
+pub fn cgroup_namespace(pid: libc::pid_t) -> Result<u64> {
+    let p = Path::new(format!("/proc/{}/ns/cgroup", pid));
+    let link = try!(fs::readlink(&p).map_err(|e| Error::from_io(&p, e)));
+    // the link is to "cgroup:[<inode>]"
+    let target = String::from_utf8_lossy(link.as_vec()).into_owned();
+    let inode = target.as_slice().splitn(1, '[').nth(1)
+                      .and_then(|rest| rest.trim_right_matches(']').parse());
+    match inode {
+        Some(inode) => Ok(inode),
+        None => Err(Error::InvalidValue(p, format!("unexpected link target {}", target))),
+    }
+}
+
+/// Whether this process is in a cgroup namespace of its own, as inside most containers. Its
+/// `/proc/self/cgroup` and the roots of its cgroup mounts are then relative to the namespace's
+/// root cgroup, which is some cgroup further down on the host.
+pub fn in_cgroup_namespace() -> Result<bool> {
+    cgroup_namespace(unsafe { libc::getpid() }).map(|ns| ns != INIT_CGROUP_NS)
+}
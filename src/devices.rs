@@ -0,0 +1,183 @@
+//! The v1 devices controller.
+
+use std::fmt;
+
+use {Controller, Result};
+
+/// What kind of device a rule covers.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DeviceType {
+    /// Every device, written `a`
+    All,
+    /// Character devices, written `c`
+    Char,
+    /// Block devices, written `b`
+    Block,
+}
+
+/// A rule for `devices.allow` or `devices.deny`, as found in `devices.list`.
+///
+/// ```rust
+/// use cgroup::devices::{DeviceRule, DeviceType};
+///
+/// // /dev/fuse
+/// let fuse = DeviceRule::new(DeviceType::Char).major(10).minor(229).read().write();
+/// assert_eq!(fuse.to_string(), "c 10:229 rw");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceRule {
+    pub kind: DeviceType,
+    /// None matches any major number
+    pub major: Option<u64>,
+    /// None matches any minor number
+    pub minor: Option<u64>,
+    pub read: bool,
+    pub write: bool,
+    pub mknod: bool,
+}
+
+impl DeviceRule {
+    /// A rule for any device of a type, granting no access yet.
+    pub fn new(kind: DeviceType) -> DeviceRule {
+        DeviceRule {
+            kind: kind,
+            major: None,
+            minor: None,
+            read: false,
+            write: false,
+            mknod: false,
+        }
+    }
+
+    /// Only match this major number.
+    pub fn major(mut self, major: u64) -> DeviceRule {
+        self.major = Some(major);
+        self
+    }
+
+    /// Only match this minor number.
+    pub fn minor(mut self, minor: u64) -> DeviceRule {
+        self.minor = Some(minor);
+        self
+    }
+
+    /// Cover reading.
+    pub fn read(mut self) -> DeviceRule {
+        self.read = true;
+        self
+    }
+
+    /// Cover writing.
+    pub fn write(mut self) -> DeviceRule {
+        self.write = true;
+        self
+    }
+
+    /// Cover creating the device node.
+    pub fn mknod(mut self) -> DeviceRule {
+        self.mknod = true;
+        self
+    }
+
+    /// Parse a rule in the kernel's syntax, like `c 10:229 rwm` or `a *:* rwm`.
+    pub fn parse(s: &str) -> Option<DeviceRule> {
+        let words: Vec<&str> = s.words().collect();
+        if words.len() != 3 {
+            return None;
+        }
+        let kind = match words[0] {
+            "a" => DeviceType::All,
+            "c" => DeviceType::Char,
+            "b" => DeviceType::Block,
+            _ => return None,
+        };
+        let number = |&: s: &str| -> Option<Option<u64>> {
+            if s == "*" { Some(None) } else { s.parse().map(Some) }
+        };
+        let mut nums = words[1].split(':');
+        let major = match nums.next().and_then(|s| number(s)) {
+            Some(m) => m,
+            None => return None,
+        };
+        let minor = match nums.next().and_then(|s| number(s)) {
+            Some(m) => m,
+            None => return None,
+        };
+
+        let mut rule = DeviceRule { major: major, minor: minor, .. DeviceRule::new(kind) };
+        for c in words[2].chars() {
+            match c {
+                'r' => rule.read = true,
+                'w' => rule.write = true,
+                'm' => rule.mknod = true,
+                _ => return None,
+            }
+        }
+        Some(rule)
+    }
+}
+
+impl fmt::String for DeviceRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            DeviceType::All => 'a',
+            DeviceType::Char => 'c',
+            DeviceType::Block => 'b',
+        };
+        try!(write!(f, "{} ", kind));
+        match self.major {
+            Some(m) => try!(write!(f, "{}:", m)),
+            None => try!(write!(f, "*:")),
+        }
+        match self.minor {
+            Some(m) => try!(write!(f, "{} ", m)),
+            None => try!(write!(f, "* ")),
+        }
+        if self.read { try!(write!(f, "r")); }
+        if self.write { try!(write!(f, "w")); }
+        if self.mknod { try!(write!(f, "m")); }
+        Ok(())
+    }
+}
+
+/// Typed access to the v1 devices controller.
+pub struct DevicesController {
+    inner: Controller,
+}
+
+impl DevicesController {
+    /// Wrap a controller handle for the devices controller.
+    pub fn new(inner: Controller) -> DevicesController {
+        DevicesController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// Grant the access a rule covers.
+    pub fn allow(&self, rule: &DeviceRule) -> Result<()> {
+        self.inner.set(b"devices.allow", rule.to_string().as_slice())
+    }
+
+    /// Revoke the access a rule covers.
+    pub fn deny(&self, rule: &DeviceRule) -> Result<()> {
+        self.inner.set(b"devices.deny", rule.to_string().as_slice())
+    }
+
+    /// The access currently granted, from `devices.list`.
+    pub fn list(&self) -> Result<Vec<DeviceRule>> {
+        let s = try!(self.inner.get(b"devices.list"));
+        let mut rules = Vec::new();
+        for line in s.as_slice().lines().filter(|l| !l.trim().is_empty()) {
+            match DeviceRule::parse(line) {
+                Some(rule) => rules.push(rule),
+                None => return Err(self.inner.invalid_value(b"devices.list", s.as_slice())),
+            }
+        }
+        Ok(rules)
+    }
+}
@@ -0,0 +1,106 @@
+//! The error type used throughout the crate.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{IoError, IoErrorKind};
+use std::os;
+use libc::{self, c_int};
+
+/// Everything that can go wrong managing cgroups. Every error carries the path it is about.
+#[derive(Clone, Show)]
+pub enum Error {
+    /// The controller isn't mounted, or this process isn't in a cgroup of its hierarchy. The path
+    /// is where it was looked for.
+    ControllerNotMounted(Path),
+    /// The control file doesn't exist, on this kernel or in this hierarchy version
+    KeyNotFound(Path),
+    /// Not allowed to read or write the file (EPERM/EACCES)
+    PermissionDenied(Path),
+    /// A value was rejected by the kernel (EINVAL), or a file had contents that couldn't be
+    /// parsed; the string says which
+    InvalidValue(Path, String),
+    /// The cgroup is in use (EBUSY), as when removing one that still has members
+    Busy(Path),
+    /// Any other IO error
+    Io(Path, IoError),
+}
+
+/// A result with this crate's `Error`.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+impl Error {
+    /// The path the error is about.
+    pub fn path(&self) -> &Path {
+        match *self {
+            Error::ControllerNotMounted(ref p) |
+            Error::KeyNotFound(ref p) |
+            Error::PermissionDenied(ref p) |
+            Error::InvalidValue(ref p, _) |
+            Error::Busy(ref p) |
+            Error::Io(ref p, _) => p,
+        }
+    }
+
+    /// Classify an error from the standard library's IO functions.
+    pub fn from_io(path: &Path, err: IoError) -> Error {
+        match err.kind {
+            IoErrorKind::FileNotFound => Error::KeyNotFound(path.clone()),
+            IoErrorKind::PermissionDenied => Error::PermissionDenied(path.clone()),
+            IoErrorKind::InvalidInput => Error::InvalidValue(path.clone(), err.desc.to_string()),
+            _ => Error::Io(path.clone(), err),
+        }
+    }
+
+    /// Classify an errno from a system call on a path.
+    pub fn from_errno(path: &Path, errno: c_int) -> Error {
+        match errno {
+            libc::ENOENT => Error::KeyNotFound(path.clone()),
+            libc::EPERM | libc::EACCES => Error::PermissionDenied(path.clone()),
+            libc::EINVAL => Error::InvalidValue(path.clone(), "rejected by the kernel".to_string()),
+            libc::ESRCH => Error::InvalidValue(path.clone(), "no such process".to_string()),
+            libc::EBUSY => Error::Busy(path.clone()),
+            _ => Error::Io(path.clone(), IoError::from_errno(errno as usize, true)),
+        }
+    }
+
+    /// Classify the errno left by the last failed system call on a path.
+    pub fn last_os_error(path: &Path) -> Error {
+        Error::from_errno(path, os::errno() as c_int)
+    }
+}
+
+impl fmt::String for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidValue(ref p, ref what) => {
+                write!(f, "{}: {}: {}", p.display(), self.description(), what)
+            },
+            Error::Io(ref p, ref err) => write!(f, "{}: {}", p.display(), err),
+            _ => write!(f, "{}: {}", self.path().display(), self.description()),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::ControllerNotMounted(_) => "controller not mounted",
+            Error::KeyNotFound(_) => "no such control file",
+            Error::PermissionDenied(_) => "permission denied",
+            Error::InvalidValue(..) => "invalid value",
+            Error::Busy(_) => "cgroup is busy",
+            Error::Io(_, ref err) => err.desc,
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            Error::Io(_, ref err) => Some(err as &StdError),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! v1 notifications through `cgroup.event_control`.
+
+use std::io::fs::PathExtensions;
+use libc;
+
+use {Controller, Error, MemoryController, Result, Version};
+use sys::{self, Fd};
+
+/// How much memory pressure to be notified about.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub enum PressureLevel {
+    /// Reclaiming memory for new allocations has started
+    Low,
+    /// Swapping or evicting active caches
+    Medium,
+    /// About to OOM
+    Critical,
+}
+
+/// Which cgroups' pressure is reported to a listener.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub enum PressureMode {
+    /// This cgroup's and its descendants', unless a listener further down consumed it
+    Default,
+    /// This cgroup's and every descendant's, regardless of other listeners
+    Hierarchy,
+    /// Only pressure on this cgroup itself
+    Local,
+}
+
+/// A blocking iterator over the notifications registered against an eventfd.
+///
+/// Each item is the number of events since the last one was read. The kernel also signals the
+/// eventfd when the cgroup is removed, after which the iterator ends.
+pub struct EventNotifier {
+    efd: Fd,
+    /// The file the notifications are about, kept open for as long as they are wanted
+    _file: Fd,
+    path: Path,
+}
+
+impl EventNotifier {
+    /// Register for notifications about `key` by writing `<eventfd> <fd of key> <args>` to
+    /// `cgroup.event_control`.
+    pub fn register(controller: &Controller, key: &[u8], args: &str) -> Result<EventNotifier> {
+        let efd = try!(Fd::eventfd(&controller.path().join(key)));
+        let file = try!(Fd::open(&controller.path().join(key), libc::O_RDONLY));
+        let line = if args.is_empty() {
+            format!("{} {}", efd.raw(), file.raw())
+        } else {
+            format!("{} {} {}", efd.raw(), file.raw(), args)
+        };
+        try!(controller.set(b"cgroup.event_control", line.as_slice()));
+        Ok(EventNotifier { efd: efd, _file: file, path: controller.path().clone() })
+    }
+
+    /// Block until the next notification, returning how many events it covers, or None once
+    /// the cgroup is gone.
+    pub fn wait(&mut self) -> Option<Result<u64>> {
+        let n = self.efd.read_u64();
+        if !self.path.exists() {
+            return None;
+        }
+        Some(n)
+    }
+
+    /// The number of events since the last read if there were any, without blocking. Fails
+    /// with `KeyNotFound` once the cgroup is gone.
+    pub fn try_wait(&mut self) -> Result<Option<u64>> {
+        if try!(self.efd.poll(sys::POLLIN, 0)) & sys::POLLIN == 0 {
+            return Ok(None);
+        }
+        match self.wait() {
+            Some(n) => n.map(Some),
+            None => Err(Error::KeyNotFound(self.path.clone())),
+        }
+    }
+
+    /// The eventfd, for use with poll(2) and friends.
+    pub fn fd(&self) -> libc::c_int {
+        self.efd.raw()
+    }
+}
+
+impl Iterator for EventNotifier {
+    type Item = Result<u64>;
+
+    fn next(&mut self) -> Option<Result<u64>> {
+        self.wait()
+    }
+}
+
+impl MemoryController {
+    /// Get notified whenever the group hits an OOM condition, through `memory.oom_control`.
+    ///
+    /// Only v1 has `cgroup.event_control`.
+    pub fn oom_events(&self) -> Result<EventNotifier> {
+        try!(self.controller().require_version(Version::V1, b"cgroup.event_control"));
+        EventNotifier::register(self.controller(), b"memory.oom_control", "")
+    }
+
+    /// Get notified about memory pressure on the group through `memory.pressure_level`.
+    ///
+    /// Only v1 has `cgroup.event_control`; v2 has PSI instead.
+    pub fn pressure_events(&self, level: PressureLevel, mode: PressureMode)
+                           -> Result<EventNotifier> {
+        try!(self.controller().require_version(Version::V1, b"cgroup.event_control"));
+        let level = match level {
+            PressureLevel::Low => "low",
+            PressureLevel::Medium => "medium",
+            PressureLevel::Critical => "critical",
+        };
+        let args = match mode {
+            PressureMode::Default => level.to_string(),
+            PressureMode::Hierarchy => format!("{},hierarchy", level),
+            PressureMode::Local => format!("{},local", level),
+        };
+        EventNotifier::register(self.controller(), b"memory.pressure_level", args.as_slice())
+    }
+}
@@ -0,0 +1,62 @@
+//! Freezing and thawing the processes of a cgroup.
+
+use {Controller, Result, Version};
+
+/// Whether the processes of a cgroup are frozen.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FreezerState {
+    /// Running normally
+    Thawed,
+    /// Asked to freeze, but not all processes have stopped yet
+    Freezing,
+    /// Every process is stopped
+    Frozen,
+}
+
+/// The freezer lives in `freezer.state` on v1, which needs a handle for the freezer
+/// hierarchy, and in `cgroup.freeze` on v2, where every cgroup has it.
+impl Controller {
+    /// Freeze every process in this cgroup. Freezing completes asynchronously; check
+    /// `freezer_state()`.
+    pub fn freeze(&self) -> Result<()> {
+        match self.version() {
+            Version::V1 => self.set(b"freezer.state", "FROZEN"),
+            Version::V2 => self.set(b"cgroup.freeze", "1"),
+        }
+    }
+
+    /// Let the processes in this cgroup run again.
+    pub fn thaw(&self) -> Result<()> {
+        match self.version() {
+            Version::V1 => self.set(b"freezer.state", "THAWED"),
+            Version::V2 => self.set(b"cgroup.freeze", "0"),
+        }
+    }
+
+    /// Where this cgroup is in freezing or thawing.
+    pub fn freezer_state(&self) -> Result<FreezerState> {
+        match self.version() {
+            Version::V1 => {
+                let s = try!(self.get(b"freezer.state"));
+                match s.as_slice().trim() {
+                    "THAWED" => Ok(FreezerState::Thawed),
+                    "FREEZING" => Ok(FreezerState::Freezing),
+                    "FROZEN" => Ok(FreezerState::Frozen),
+                    _ => Err(self.invalid_value(b"freezer.state", s.as_slice())),
+                }
+            },
+            Version::V2 => {
+                if !try!(self.get_bool(b"cgroup.freeze")) {
+                    return Ok(FreezerState::Thawed);
+                }
+                // cgroup.freeze is what was asked for, cgroup.events says whether it's done
+                let events = try!(self.get_flat_keyed(b"cgroup.events"));
+                match events.get("frozen") {
+                    Some(&1) => Ok(FreezerState::Frozen),
+                    _ => Ok(FreezerState::Freezing),
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! The hugetlb controller.
+
+use std::io::fs::PathExtensions;
+
+use {Controller, Limit, Result, Version};
+
+/// The size in bytes of a huge page size as the kernel names it in file names, like `2MB`.
+pub fn page_size_bytes(size: &str) -> Option<u64> {
+    let split = size.find(|&: c: char| !c.is_digit(10)).unwrap_or(size.len());
+    let n: u64 = match size[..split].parse() {
+        Some(n) => n,
+        None => return None,
+    };
+    let unit = match &size[split..] {
+        "KB" => 1 << 10,
+        "MB" => 1 << 20,
+        "GB" => 1 << 30,
+        _ => return None,
+    };
+    Some(n * unit)
+}
+
+/// Typed access to the hugetlb controller. Every page size has its own set of files, named
+/// like `hugetlb.2MB.max`.
+pub struct HugetlbController {
+    inner: Controller,
+}
+
+impl HugetlbController {
+    /// Wrap a controller handle for the hugetlb controller.
+    pub fn new(inner: Controller) -> HugetlbController {
+        HugetlbController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// The huge page sizes the kernel supports, like `2MB` and `1GB`.
+    pub fn page_sizes(&self) -> Result<Vec<String>> {
+        let suffix = match self.inner.version() {
+            Version::V1 => ".limit_in_bytes",
+            Version::V2 => ".max",
+        };
+        let mut sizes = Vec::new();
+        for p in try!(::read_dir(self.inner.path())).into_iter() {
+            if !p.is_file() { continue; }
+            let name = match p.filename_str() {
+                Some(n) => n,
+                None => continue,
+            };
+            if name.starts_with("hugetlb.") && name.ends_with(suffix) {
+                let size = &name["hugetlb.".len()..name.len() - suffix.len()];
+                // rsvd.max and friends also end in .max
+                if !size.contains_char('.') {
+                    sizes.push(size.to_string());
+                }
+            }
+        }
+        sizes.sort_by(|a, b| page_size_bytes(a.as_slice()).cmp(&page_size_bytes(b.as_slice())));
+        Ok(sizes)
+    }
+
+    fn key(&self, size: &str, v1: &str, v2: &str) -> Vec<u8> {
+        let file = match self.inner.version() {
+            Version::V1 => v1,
+            Version::V2 => v2,
+        };
+        format!("hugetlb.{}.{}", size, file).into_bytes()
+    }
+
+    /// Bytes of huge pages of a size in use.
+    pub fn usage(&self, size: &str) -> Result<u64> {
+        self.inner.get_u64(self.key(size, "usage_in_bytes", "current").as_slice())
+    }
+
+    /// The limit on bytes of huge pages of a size.
+    pub fn limit(&self, size: &str) -> Result<Limit> {
+        let key = self.key(size, "limit_in_bytes", "max");
+        let s = try!(self.inner.get(key.as_slice()));
+        // v1 has no "max", just i64::MAX rounded down to the page size
+        let unlimited = ::std::i64::MAX as u64 & !(page_size_bytes(size).unwrap_or(1) - 1);
+        match (self.inner.version(), Limit::parse(s.as_slice())) {
+            (Version::V1, Some(Limit::Value(v))) if v >= unlimited => Ok(Limit::Max),
+            (_, Some(l)) => Ok(l),
+            (_, None) => Err(self.inner.invalid_value(key.as_slice(), s.as_slice())),
+        }
+    }
+
+    /// Set the limit on bytes of huge pages of a size.
+    pub fn set_limit(&self, size: &str, limit: Limit) -> Result<()> {
+        let value = match self.inner.version() {
+            Version::V1 => limit.to_v1_string(),
+            Version::V2 => limit.to_string(),
+        };
+        self.inner.set(self.key(size, "limit_in_bytes", "max").as_slice(), value.as_slice())
+    }
+}
@@ -0,0 +1,190 @@
+//! Watching v2 event files with inotify.
+//!
+//! The kernel signals a file modification on files like `memory.events` and `cgroup.events`
+//! whenever their contents change.
+
+use std::mem;
+use time;
+
+use {Controller, Error, MemoryController, Result, Version};
+use memory::MemoryEvents;
+use sys::{self, Fd};
+
+/// Blocks until a control file changes.
+pub struct FileWatcher {
+    fd: Fd,
+}
+
+impl FileWatcher {
+    /// Watch one control file of a controller for modifications.
+    pub fn new(controller: &Controller, key: &[u8]) -> Result<FileWatcher> {
+        let fd = try!(Fd::inotify(&controller.path().join(key), sys::IN_MODIFY));
+        Ok(FileWatcher { fd: fd })
+    }
+
+    /// Block until the file has been modified at least once since the last call. Fails with
+    /// `KeyNotFound` once the file is gone, as when its cgroup is removed.
+    pub fn wait(&self) -> Result<()> {
+        // the events hardly matter, only that there were some and whether the watch went away
+        let mut buf = [0u8; 4096];
+        let n = try!(self.fd.read(&mut buf));
+        // struct inotify_event: wd, mask, cookie and len, then len bytes of name
+        let mut i = 0;
+        while i + 16 <= n {
+            let field = |&: at: usize| {
+                let mut bytes = [0u8; 4];
+                for (b, &v) in bytes.iter_mut().zip(buf[at..at + 4].iter()) { *b = v; }
+                unsafe { mem::transmute::<[u8; 4], u32>(bytes) }
+            };
+            let (mask, len) = (field(i + 4), field(i + 12) as usize);
+            if mask & sys::IN_IGNORED != 0 {
+                return Err(Error::KeyNotFound(self.fd.path().clone()));
+            }
+            i += 16 + len;
+        }
+        Ok(())
+    }
+
+    /// Like `wait`, giving up after `timeout_ms` milliseconds. False on timeout.
+    pub fn wait_timeout(&self, timeout_ms: i32) -> Result<bool> {
+        if try!(self.fd.poll(sys::POLLIN, timeout_ms)) == 0 {
+            return Ok(false);
+        }
+        try!(self.wait());
+        Ok(true)
+    }
+
+    /// Whether the file has been modified since the last call, without blocking.
+    pub fn try_wait(&self) -> Result<bool> {
+        self.wait_timeout(0)
+    }
+
+    /// The inotify descriptor, for use with poll(2) and friends.
+    pub fn fd(&self) -> ::libc::c_int {
+        self.fd.raw()
+    }
+}
+
+/// A blocking iterator over changes to `memory.events` or `memory.events.local`, yielding how
+/// much each counter went up.
+pub struct MemoryEventsWatcher {
+    watcher: FileWatcher,
+    controller: Controller,
+    key: &'static [u8],
+    last: MemoryEvents,
+    /// The file went away, ending the iteration
+    done: bool,
+}
+
+impl MemoryEventsWatcher {
+    /// Block until a counter changes and return the increases since the last reading.
+    pub fn wait(&mut self) -> Result<MemoryEvents> {
+        loop {
+            try!(self.watcher.wait());
+            if let Some(delta) = try!(self.update()) {
+                return Ok(delta);
+            }
+        }
+    }
+
+    /// The increases since the last reading if a counter changed, without blocking.
+    pub fn try_wait(&mut self) -> Result<Option<MemoryEvents>> {
+        if !try!(self.watcher.try_wait()) {
+            return Ok(None);
+        }
+        self.update()
+    }
+
+    /// Read the counters again, returning the increases if there were any.
+    fn update(&mut self) -> Result<Option<MemoryEvents>> {
+        let now: MemoryEvents = try!(self.controller.get_flat_keyed_as(self.key));
+        let delta = now.since(&self.last);
+        self.last = now;
+        Ok(if delta != Default::default() { Some(delta) } else { None })
+    }
+
+    /// The inotify descriptor, for use with poll(2) and friends.
+    pub fn fd(&self) -> ::libc::c_int {
+        self.watcher.fd()
+    }
+}
+
+impl Iterator for MemoryEventsWatcher {
+    type Item = Result<MemoryEvents>;
+
+    /// Ends once the cgroup is gone.
+    fn next(&mut self) -> Option<Result<MemoryEvents>> {
+        if self.done {
+            return None;
+        }
+        match self.wait() {
+            Err(Error::KeyNotFound(_)) => {
+                self.done = true;
+                None
+            },
+            r => Some(r),
+        }
+    }
+}
+
+impl MemoryController {
+    /// Watch the group's `memory.events`, or `memory.events.local` to leave out events in
+    /// descendants. Only v2 has these files.
+    pub fn watch_events(&self, local: bool) -> Result<MemoryEventsWatcher> {
+        let key: &'static [u8] = if local { b"memory.events.local" } else { b"memory.events" };
+        try!(self.controller().require_version(Version::V2, key));
+        // watch first so a change between the two isn't missed
+        let watcher = try!(FileWatcher::new(self.controller(), key));
+        let last = try!(self.controller().get_flat_keyed_as(key));
+        Ok(MemoryEventsWatcher {
+            watcher: watcher,
+            controller: try!(self.controller().reopen()),
+            key: key,
+            last: last,
+            done: false,
+        })
+    }
+}
+
+impl Controller {
+    /// Whether this cgroup or any of its descendants has processes in it, from the v2
+    /// `cgroup.events`.
+    pub fn populated(&self) -> Result<bool> {
+        let events = try!(self.get_flat_keyed(b"cgroup.events"));
+        match events.get("populated") {
+            Some(&p) => Ok(p != 0),
+            None => Err(Error::InvalidValue(self.path().join("cgroup.events"),
+                                            "no populated field".to_string())),
+        }
+    }
+
+    /// Block until every process in this cgroup and its descendants has exited or moved out.
+    pub fn wait_empty(&self) -> Result<()> {
+        self.wait_empty_timeout(-1).map(|_| ())
+    }
+
+    /// Like `wait_empty`, giving up after `timeout_ms` milliseconds in all, however often
+    /// `cgroup.events` changes in the meantime; -1 waits forever. False on timeout.
+    pub fn wait_empty_timeout(&self, timeout_ms: i32) -> Result<bool> {
+        let deadline_ns = if timeout_ms < 0 {
+            None
+        } else {
+            Some(time::precise_time_ns() + timeout_ms as u64 * 1000000)
+        };
+        // watch first so the last process leaving between the check and the wait isn't missed
+        let watcher = try!(FileWatcher::new(self, b"cgroup.events"));
+        while try!(self.populated()) {
+            let remaining_ms = match deadline_ns {
+                None => -1,
+                Some(deadline) => {
+                    let now = time::precise_time_ns();
+                    if now >= deadline { 0 } else { ((deadline - now) / 1000000) as i32 }
+                },
+            };
+            if !try!(watcher.wait_timeout(remaining_ms)) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
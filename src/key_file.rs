@@ -0,0 +1,77 @@
+//! Control files kept open, for reading the same key again and again.
+
+use libc;
+
+use {Controller, Error, Result};
+use sys::Fd;
+
+/// An open control file, read from the start each time with `pread(2)`, which makes the
+/// kernel generate the contents afresh. This saves the open and close of every `get` when
+/// sampling often.
+///
+/// Once the cgroup is removed reads fail (with ENODEV), and the file has to be opened again
+/// from a new cgroup of the same name, if one appears.
+pub struct KeyFile {
+    fd: Fd,
+}
+
+impl KeyFile {
+    /// Open a control file for reading.
+    pub fn open(path: &Path) -> Result<KeyFile> {
+        Fd::open(path, libc::O_RDONLY).map(|fd| KeyFile { fd: fd })
+    }
+
+    /// Read the whole file.
+    pub fn read(&self) -> Result<String> {
+        self.read_bytes().map(|b| String::from_utf8_lossy(b.as_slice()).into_owned())
+    }
+
+    /// Read the whole file as raw bytes.
+    pub fn read_bytes(&self) -> Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            match try!(self.fd.pread(&mut buf, contents.len() as u64)) {
+                0 => return Ok(contents),
+                n => contents.push_all(&buf[..n]),
+            }
+        }
+    }
+
+    /// Read the whole file into `buf`, returning how many bytes it held. A file that doesn't
+    /// fit is `InvalidValue`, rather than cut short.
+    pub fn read_into(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut len = 0;
+        while len < buf.len() {
+            match try!(self.fd.pread(&mut buf[len..], len as u64)) {
+                0 => return Ok(len),
+                n => len += n,
+            }
+        }
+        let mut more = [0u8; 1];
+        match try!(self.fd.pread(&mut more, len as u64)) {
+            0 => Ok(len),
+            _ => Err(Error::InvalidValue(self.path().clone(),
+                                         format!("larger than the {} byte buffer", buf.len()))),
+        }
+    }
+
+    /// The path of the file.
+    pub fn path(&self) -> &Path {
+        self.fd.path()
+    }
+}
+
+impl Controller {
+    /// Open a key to read it repeatedly, failing with `KeyNotFound` if it doesn't exist.
+    pub fn open_key(&self, key: &[u8]) -> Result<KeyFile> {
+        KeyFile::open(&self.key_path(key))
+    }
+
+    /// Read a key into a buffer the caller reuses, returning how many bytes it held, so
+    /// sampling doesn't allocate a `String` each time. A value that doesn't fit is
+    /// `InvalidValue`.
+    pub fn read_into(&self, key: &[u8], buf: &mut [u8]) -> Result<usize> {
+        try!(self.open_key(key)).read_into(buf)
+    }
+}
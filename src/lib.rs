@@ -12,29 +12,231 @@
 //! }
 //! ```
 
+#![cfg_attr(feature = "serde", feature(plugin))]
+
 extern crate libc;
+extern crate time;
+#[cfg(feature = "serde")]
+#[plugin] #[no_link]
+extern crate serde_macros;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "systemd")]
+extern crate dbus;
+#[cfg(feature = "config")]
+extern crate toml;
+#[cfg(feature = "config")]
+extern crate "rustc-serialize" as rustc_serialize;
+
+pub use blkio::{BlkioController, CostControl, IoController, IoCostModel, IoCostQos, IoLimits,
+                IoStat, Throttle, device_of};
+pub use cgroup_fd::CgroupFd;
+pub use command::{CgroupCommand, CgroupCommandExt, Child};
+pub use cpu::{CpuController, CpuStat, CpuTimes, CpuUsage, Utilization};
+pub use cpuset::CpusetController;
+pub use detect::{Layout, cgroup_namespace, in_cgroup_namespace};
+pub use devices::DevicesController;
+pub use error::{Error, Result};
+pub use events::{EventNotifier, PressureLevel, PressureMode};
+pub use freezer::FreezerState;
+pub use hugetlb::HugetlbController;
+pub use limit::Limit;
+pub use mountinfo::Mount;
+pub use inotify::{FileWatcher, MemoryEventsWatcher};
+pub use key_file::KeyFile;
+pub use memory::{MemoryController, MemoryEvents, MemoryProtection};
+pub use misc::MiscController;
+pub use net_cls::{ClassId, NetClsController};
+pub use net_prio::NetPrioController;
+pub use parse::{Device, FlatKeyed};
+pub use pids::{PidsController, PidsEvents};
+pub use psi::{Pressure, Trigger};
+pub use rdma::{RdmaController, RdmaResources};
+pub use resources::{LimitsBuilder, Resources};
+pub use sampler::{Sample, Sampler};
+pub use snapshot::{Change, Snapshot, SnapshotDiff, Timestamp};
+pub use sys::Fd;
+pub use tree::{Children, Walk, WalkEntry};
+pub use unified::{CgroupType, ControllerSet};
+pub use watch_set::{Event, Token, Watch, WatchSet};
 
 use std::collections::HashMap;
-use std::cell::RefCell;
-use std::io::{File, IoResult};
-use std::io::fs::PathExtensions;
+use std::io::File;
+use std::io::fs::{self, PathExtensions};
+use std::io::timer;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+
+#[macro_use]
+mod parse;
+mod blkio;
+#[cfg(feature = "bpf")]
+pub mod bpf;
+mod cgroup_fd;
+mod command;
+pub mod compat;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod container;
+mod cpu;
+pub mod cpuset;
+mod detect;
+pub mod devices;
+mod error;
+mod events;
+mod freezer;
+pub mod hugetlb;
+mod inotify;
+mod key_file;
+mod limit;
+mod memory;
+mod misc;
+pub mod mountinfo;
+mod net_cls;
+mod net_prio;
+#[cfg(feature = "async")]
+pub mod nonblock;
+mod perf_event;
+mod pids;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+pub mod psi;
+mod rdma;
+mod resources;
+mod sampler;
+pub mod schema;
+pub mod size;
+mod snapshot;
+#[cfg(feature = "statsd")]
+pub mod statsd;
+mod sys;
+pub mod system;
+#[cfg(feature = "systemd")]
+pub mod systemd;
+mod tree;
+mod unified;
+pub mod unit;
+mod watch_set;
 
 pub struct CGroup {
-    /// Path to the cgroup control filesystem
+    /// Path to the cgroup control filesystem, for hierarchies not found among the mounts
     basepath: Path,
+    /// Mapping from v1 hierarchy name (its controllers, comma separated) to where it is mounted
+    mounts: HashMap<Vec<u8>, Path>,
+    /// Where the unified hierarchy is mounted, if it was found among the mounts
+    unified_mount: Option<Path>,
+    /// The superblock options of the unified hierarchy's mount, if it was found
+    unified_options: Vec<String>,
+    /// Which cgroup each mounted v1 hierarchy's mount shows, when it isn't the root: a bind
+    /// mount of part of the tree, or a mount from outside this process's cgroup namespace
+    roots: HashMap<Vec<u8>, Path>,
+    /// Which cgroup the unified hierarchy's mount shows, if that isn't the root
+    unified_root: Option<Path>,
     /// Mapping from controller name to relative path from the basepath of that controller's
     /// directory
     controllers: HashMap<Vec<u8>, Path>,
+    /// Path of the process in the unified (cgroup2) hierarchy, if it is in one
+    unified: Option<Path>,
+}
+
+/// Which hierarchy a controller lives in.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Version {
+    /// One of the legacy per-controller hierarchies, `<basepath>/<controller>/...`
+    V1,
+    /// The unified hierarchy, where every controller shares one directory
+    V2,
+}
+
+/// What `Controller::remove_recursive` does with processes still attached to the tree.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub enum RemoveMode {
+    /// Leave them be, so removal fails with `Busy` on any cgroup that still has members
+    Strict,
+    /// Move them to the parent of the tree being removed, then remove
+    MigrateToParent,
 }
 
+/// What happened to each write of `Controller::set_many`, in order.
+#[derive(Show)]
+pub struct SetReport {
+    /// Each key written, with how it went
+    pub results: Vec<(Vec<u8>, Result<()>)>,
+    /// The keys not written because an earlier one failed
+    pub skipped: Vec<Vec<u8>>,
+}
+
+impl SetReport {
+    /// Whether every value was written.
+    pub fn is_ok(&self) -> bool {
+        self.skipped.is_empty() && self.results.iter().all(|&(_, ref r)| r.is_ok())
+    }
+
+    /// The writes that failed.
+    pub fn failures(&self) -> Vec<&(Vec<u8>, Result<()>)> {
+        self.results.iter().filter(|&&(_, ref r)| r.is_err()).collect()
+    }
+}
+
+/// How long a `Controller` trusts what it has learned of the files in its directory.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub enum CachePolicy {
+    /// Remember a file once it's been seen, for the life of the handle
+    Forever,
+    /// Forget what's been seen once it is older than this
+    Ttl(Duration),
+    /// Don't remember anything, and look at the directory on every access
+    Disabled,
+}
+
+/// A handle on one cgroup directory. It can be shared between threads, which read keys
+/// concurrently; only learning about new files takes the cache's lock exclusively.
+///
+/// Making a handle doesn't list the directory: keys are looked up as they're used, and the
+/// files found are remembered, so a handle works where the directory can't be read.
 pub struct Controller {
     path: Path,
-    cache: RefCell<HashMap<Vec<u8>, Path>>,
+    version: Version,
+    cache: RwLock<PathCache>,
+    policy: CachePolicy,
+}
+
+/// The files of a controller's directory seen so far.
+struct PathCache {
+    files: HashMap<Vec<u8>, Path>,
+    /// Monotonic clock in nanoseconds when the cache was last emptied or filled
+    since_ns: u64,
+}
+
+impl PathCache {
+    fn new(files: HashMap<Vec<u8>, Path>) -> PathCache {
+        PathCache { files: files, since_ns: time::precise_time_ns() }
+    }
 }
 
 /// Get the controller mappings for a process.
-pub fn get_controllers(pid: libc::pid_t) -> IoResult<HashMap<Vec<u8>, Path>> {
-    let contents = try!(File::open(&Path::new(format!("/proc/{}/cgroup", pid))).read_to_string());
+///
+/// The unified hierarchy shows up as `0::<path>`, so its path is stored under the empty name.
+/// Paths are relative to the root of the calling process's cgroup namespace; hierarchies in
+/// which the process is outside of it (shown as `/..`) are left out.
+pub fn get_controllers(pid: libc::pid_t) -> Result<HashMap<Vec<u8>, Path>> {
+    read_controllers(&Path::new(format!("/proc/{}/cgroup", pid)))
+}
+
+/// Get the controller mappings for a single thread of a process, like `get_controllers`.
+///
+/// Threads can be apart from the rest of their process in v1 hierarchies and in threaded v2
+/// cgroups.
+pub fn get_thread_controllers(pid: libc::pid_t, tid: libc::pid_t)
+                              -> Result<HashMap<Vec<u8>, Path>> {
+    read_controllers(&Path::new(format!("/proc/{}/task/{}/cgroup", pid, tid)))
+}
+
+fn read_controllers(proc_path: &Path) -> Result<HashMap<Vec<u8>, Path>> {
+    let contents = try!(File::open(proc_path).read_to_string()
+                        .map_err(|e| Error::from_io(proc_path, e)));
     let mut map = HashMap::new();
     for line in contents.as_slice().lines() {
         let mut columns = line.split(':').fuse();
@@ -43,16 +245,19 @@ pub fn get_controllers(pid: libc::pid_t) -> IoResult<HashMap<Vec<u8>, Path>> {
             None => break
         }
         let name: &str = columns.next().expect("No controller name!");
-        let path = Path::new(columns.next().expect("No controller path!"));
-        map.insert(name.bytes().collect(), path);
+        let path: &str = columns.next().expect("No controller path!");
+        if path == "/.." || path.starts_with("/../") {
+            continue;
+        }
+        map.insert(name.bytes().collect(), Path::new(path));
     }
     Ok(map)
 }
 
-fn path_cache(path: &Path) -> IoResult<HashMap<Vec<u8>, Path>> {
+fn path_cache(path: &Path) -> Result<HashMap<Vec<u8>, Path>> {
     let mut map = HashMap::new();
-    for path in try!(std::io::fs::readdir(path)).into_iter() {
-        if !path.is_file() { break; }
+    for path in try!(fs::readdir(path).map_err(|e| Error::from_io(path, e))).into_iter() {
+        if !path.is_file() { continue; }
         let fname = path.filename().expect("Invalid path returned by readdir?").iter().map(|&x| x).collect();
         map.insert(fname, path);
     }
@@ -60,56 +265,775 @@ fn path_cache(path: &Path) -> IoResult<HashMap<Vec<u8>, Path>> {
 }
 
 impl CGroup {
-    /// Get the CGroup for the current process.
-    pub fn new() -> IoResult<CGroup> {
-        CGroup::from_base_and_pid(Path::new("/sys/fs/cgroup"), unsafe { libc::getpid() })
+    /// Get the CGroup for the current process. The same as `detect`.
+    pub fn new() -> Result<CGroup> {
+        CGroup::detect()
+    }
+
+    /// Get the CGroup for the current process, working out where its hierarchies are mounted
+    /// and whether the system is v1, v2 or hybrid (see `layout` and `version_of`).
+    pub fn detect() -> Result<CGroup> {
+        CGroup::from_pid(unsafe { libc::getpid() })
+    }
+
+    /// Get the CGroup for a process, finding each hierarchy where this process has mounted it
+    /// according to `/proc/self/mountinfo`.
+    ///
+    /// The process's paths and the mounts' roots are both seen from this process's cgroup
+    /// namespace, so this works for a process in a container as well as for a containerized
+    /// process seen from the host.
+    pub fn from_pid(pid: libc::pid_t) -> Result<CGroup> {
+        CGroup::from_base_and_pid(Path::new("/sys/fs/cgroup"), pid).and_then(CGroup::find_mounts)
+    }
+
+    /// Get the CGroup for a single thread of a process, like `from_pid`.
+    pub fn from_thread(pid: libc::pid_t, tid: libc::pid_t) -> Result<CGroup> {
+        let conts = try!(get_thread_controllers(pid, tid));
+        CGroup::from_base_and_controllers(Path::new("/sys/fs/cgroup"), conts)
+            .and_then(CGroup::find_mounts)
+    }
+
+    /// Fill in the mount points of the hierarchies from `/proc/self/mountinfo`.
+    fn find_mounts(mut cg: CGroup) -> Result<CGroup> {
+        for m in try!(mountinfo::cgroup_mounts()).into_iter() {
+            let root = if m.root == Path::new("/") { None } else { Some(m.root.clone()) };
+            if m.is_cgroup2() {
+                if cg.unified_mount.is_none() {
+                    cg.unified_mount = Some(m.mount_point);
+                    cg.unified_options = m.super_options;
+                    cg.unified_root = root;
+                }
+            } else {
+                let name = m.hierarchy_name();
+                if !name.is_empty() && !cg.mounts.contains_key(&name) {
+                    if let Some(root) = root {
+                        cg.roots.insert(name.clone(), root);
+                    }
+                    cg.mounts.insert(name, m.mount_point);
+                }
+            }
+        }
+        Ok(cg)
+    }
+
+    /// Get the CGroup for a process using a given basepath, with each hierarchy mounted at
+    /// `<basepath>/<name>`
+    pub fn from_base_and_pid(base: Path, pid: libc::pid_t) -> Result<CGroup> {
+        CGroup::from_base_and_controllers(base, try!(get_controllers(pid)))
     }
 
-    /// Get the CGroup for a process using a given basepath
-    pub fn from_base_and_pid(base: Path, pid: libc::pid_t) -> IoResult<CGroup> {
-        let conts = try!(get_controllers(pid));
+    fn from_base_and_controllers(base: Path, mut conts: HashMap<Vec<u8>, Path>)
+                                 -> Result<CGroup> {
+        let unified = conts.remove(b"");
 
         Ok(CGroup {
             basepath: base,
-            controllers: conts
+            mounts: HashMap::new(),
+            unified_mount: None,
+            unified_options: Vec::new(),
+            roots: HashMap::new(),
+            unified_root: None,
+            controllers: conts,
+            unified: unified,
         })
     }
 
-    /// Get a controller from this cgroup, returning None if the named controller is not present.
-    pub fn controller(&self, name: &[u8]) -> Option<Controller> {
-        let mut p = self.basepath.join(name);
-        match self.controllers.get(name) {
-            // remove the leading / to make the path "relative"
-            Some(c) => p.push(c.path_relative_from(&Path::new("/")).expect("path_relative_from is bork?")),
-            None => return None
+    /// The v1 hierarchy a controller is in, named as in `/proc/<pid>/cgroup`: the name given
+    /// if it's a hierarchy, or else the co-mounted set it's part of, like `cpu,cpuacct` for
+    /// `cpu`.
+    fn hierarchy_key(&self, name: &[u8]) -> Option<&[u8]> {
+        self.controllers.keys()
+            .find(|h| h.as_slice() == name || h.as_slice().split(|&b| b == b',').any(|c| c == name))
+            .map(|h| h.as_slice())
+    }
+
+    /// Where a v1 hierarchy is mounted. Unless it was found among the mounts, a hierarchy with
+    /// only a name, like `name=systemd`, sits in `<basepath>/<name>`.
+    fn hierarchy_base(&self, name: &[u8]) -> Path {
+        match self.mounts.get(name) {
+            Some(p) => p.clone(),
+            None if name.starts_with(b"name=") && !name.contains(&b',') => {
+                self.basepath.join(&name[b"name=".len()..])
+            },
+            None => self.basepath.join(name),
         }
-        let cache = match path_cache(&p) {
-            Ok(cache) => cache,
-            Err(_) => return None,
+    }
+
+    /// Where the unified hierarchy is mounted.
+    ///
+    /// Unless it was found among the mounts, on a pure cgroup2 system it is the basepath itself,
+    /// and on a hybrid system it sits in `<basepath>/unified`.
+    fn unified_base(&self) -> Path {
+        if let Some(ref p) = self.unified_mount {
+            return p.clone();
+        }
+        if self.basepath.join("cgroup.controllers").is_file() {
+            self.basepath.clone()
+        } else {
+            self.basepath.join("unified")
+        }
+    }
+
+    /// Directory of this process in the unified hierarchy, None if it isn't in one (or isn't
+    /// below the mount's root).
+    fn unified_path(&self) -> Option<Path> {
+        self.unified.as_ref()
+            .and_then(|u| relative_to(u, self.unified_root.as_ref()))
+            .map(|u| self.unified_base().join(u))
+    }
+
+    /// The controllers available in this process's unified cgroup, as listed by
+    /// `cgroup.controllers`. Empty if the process isn't in a unified hierarchy.
+    pub fn unified_controllers(&self) -> Result<ControllerSet> {
+        let p = match self.unified_path() {
+            Some(p) => p.join("cgroup.controllers"),
+            None => return Ok(Default::default()),
         };
+        read_file(&p).map(|s| ControllerSet::parse(s.as_slice()))
+    }
 
-        Some(Controller {
-            path: p,
-            cache: RefCell::new(cache),
-        })
+    /// Get this process's cgroup in a named v1 hierarchy, one mounted with `name=`, like the
+    /// `systemd` hierarchy systemd keeps for tracking processes when it doesn't use the unified
+    /// one. Fails with `ControllerNotMounted` if the process isn't in one of that name.
+    pub fn named(&self, name: &str) -> Result<Controller> {
+        let key = format!("name={}", name).into_bytes();
+        match self.hierarchy_key(key.as_slice()) {
+            Some(_) => self.controller(key.as_slice()),
+            None => Err(Error::ControllerNotMounted(self.basepath.join(name))),
+        }
+    }
+
+    /// The names of the named v1 hierarchies this process is in.
+    pub fn named_hierarchies(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for hierarchy in self.controllers.keys() {
+            for n in hierarchy.as_slice().split(|&b| b == b',') {
+                if n.starts_with(b"name=") {
+                    names.push(String::from_utf8_lossy(&n[b"name=".len()..]).into_owned());
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// Get a handle for this process's cgroup in the unified hierarchy, for the core `cgroup.*`
+    /// files every v2 cgroup has.
+    pub fn unified(&self) -> Result<Controller> {
+        match self.unified_path() {
+            Some(p) => Controller::at(p, Version::V2),
+            None => Err(Error::ControllerNotMounted(self.unified_base())),
+        }
+    }
+
+    /// Get a controller from this cgroup, failing with `ControllerNotMounted` if the named
+    /// controller is not present.
+    ///
+    /// Controllers in a v1 hierarchy take precedence, found by any of the names of a
+    /// co-mounted hierarchy like `cpu,cpuacct`; otherwise the controller is looked up in the
+    /// unified hierarchy's `cgroup.controllers`.
+    pub fn controller(&self, name: &[u8]) -> Result<Controller> {
+        let hierarchy = self.hierarchy_key(name)
+                            .and_then(|h| self.controllers.get(h).map(|c| (h, c)));
+        let (p, version) = match hierarchy {
+            Some((h, c)) => match relative_to(c, self.roots.get(h)) {
+                Some(rel) => (self.hierarchy_base(h).join(rel), Version::V1),
+                None => return Err(Error::ControllerNotMounted(self.hierarchy_base(h))),
+            },
+            None => match self.unified_path() {
+                Some(p) => {
+                    if !try!(self.unified_controllers()).contains(name) {
+                        return Err(Error::ControllerNotMounted(self.basepath.join(name)));
+                    }
+                    (p, Version::V2)
+                },
+                None => return Err(Error::ControllerNotMounted(self.basepath.join(name))),
+            },
+        };
+        Controller::at(p, version)
+    }
+
+    /// Get the memory controller of this cgroup.
+    pub fn memory(&self) -> Result<MemoryController> {
+        self.controller(b"memory").map(MemoryController::new)
+    }
+
+    /// Get the cpu controller of this cgroup.
+    pub fn cpu(&self) -> Result<CpuController> {
+        self.controller(b"cpu").map(CpuController::new)
+    }
+
+    /// Get the cpuset controller of this cgroup.
+    pub fn cpuset(&self) -> Result<CpusetController> {
+        self.controller(b"cpuset").map(CpusetController::new)
+    }
+
+    /// Get the v2 io controller of this cgroup.
+    pub fn io(&self) -> Result<IoController> {
+        self.controller(b"io").map(IoController::new)
+    }
+
+    /// Get the v1 blkio controller of this cgroup.
+    pub fn blkio(&self) -> Result<BlkioController> {
+        self.controller(b"blkio").map(BlkioController::new)
+    }
+
+    /// Get the pids controller of this cgroup.
+    pub fn pids(&self) -> Result<PidsController> {
+        self.controller(b"pids").map(PidsController::new)
+    }
+
+    /// Get a handle to freeze this cgroup with: the v1 freezer hierarchy if there is one,
+    /// otherwise the unified hierarchy.
+    pub fn freezer(&self) -> Result<Controller> {
+        self.controller(b"freezer").or_else(|_| self.unified())
+    }
+
+    /// Get the v1 devices controller of this cgroup.
+    pub fn devices(&self) -> Result<DevicesController> {
+        self.controller(b"devices").map(DevicesController::new)
+    }
+
+    /// Get the hugetlb controller of this cgroup.
+    pub fn hugetlb(&self) -> Result<HugetlbController> {
+        self.controller(b"hugetlb").map(HugetlbController::new)
+    }
+
+    /// Get the v1 net_cls controller of this cgroup.
+    pub fn net_cls(&self) -> Result<NetClsController> {
+        self.controller(b"net_cls").map(NetClsController::new)
+    }
+
+    /// Get the v1 net_prio controller of this cgroup.
+    pub fn net_prio(&self) -> Result<NetPrioController> {
+        self.controller(b"net_prio").map(NetPrioController::new)
+    }
+
+    /// Get the rdma controller of this cgroup.
+    pub fn rdma(&self) -> Result<RdmaController> {
+        self.controller(b"rdma").map(RdmaController::new)
+    }
+
+    /// Get the v2 misc controller of this cgroup.
+    pub fn misc(&self) -> Result<MiscController> {
+        self.controller(b"misc").map(MiscController::new)
+    }
+
+    /// Create a new cgroup called `name` beneath this process's cgroup for the named controller.
+    pub fn create(&self, controller: &[u8], name: &str) -> Result<Controller> {
+        self.controller(controller).and_then(|c| c.create_child(name))
+    }
+}
+
+/// Remove the leading / from a cgroup path to make it "relative"
+fn relative(path: &Path) -> Path {
+    path.path_relative_from(&Path::new("/")).expect("path_relative_from is bork?")
+}
+
+/// Make a cgroup path relative to the cgroup a mount shows, None if it's outside of it.
+fn relative_to(path: &Path, root: Option<&Path>) -> Option<Path> {
+    match root {
+        Some(root) if root.is_ancestor_of(path) => path.path_relative_from(root),
+        Some(_) => None,
+        None => Some(relative(path)),
     }
 }
 
 impl Controller {
-    /// Get a value for a key in this controller, None if the key doesn't exist
-    pub fn get(&self, key: &[u8]) -> Option<IoResult<String>> {
-        if !self.cache.borrow().contains_key(key) {
-            self.cache.borrow_mut().insert(key.iter().map(|&x| x).collect(), self.path.join(key));
+    /// Build a controller handle for a cgroup directory, failing with `KeyNotFound` if there
+    /// is no such directory.
+    fn at(path: Path, version: Version) -> Result<Controller> {
+        if !path.is_dir() {
+            return Err(Error::KeyNotFound(path));
         }
+        Ok(Controller {
+            path: path,
+            version: version,
+            cache: RwLock::new(PathCache::new(HashMap::new())),
+            policy: CachePolicy::Forever,
+        })
+    }
 
-        let cache = self.cache.borrow();
-        let p = cache.get(key).expect("Cache didn't cache a key!");
+    /// Use a different policy for remembering files, `Forever` by default. Long-lived handles
+    /// on cgroups that lose files, as when controllers are disabled, want a `Ttl` or `refresh`
+    /// now and then.
+    pub fn with_cache_policy(mut self, policy: CachePolicy) -> Controller {
+        self.policy = policy;
+        if policy == CachePolicy::Disabled {
+            self.cache.write().unwrap().files.clear();
+        }
+        self
+    }
+
+    /// The policy for remembering files.
+    pub fn cache_policy(&self) -> CachePolicy {
+        self.policy
+    }
 
-        if !p.exists() && !p.is_file() {
+    /// List the directory, learning every file at once and forgetting those that are gone.
+    /// Unlike the rest of the handle, this needs the directory to be readable.
+    pub fn refresh(&self) -> Result<()> {
+        self.keys().map(|_| ())
+    }
+
+    /// The names of the control files in the directory, sorted, for finding out which ones
+    /// this kernel has. The directory is listed afresh, and what's found is remembered as with
+    /// `refresh`.
+    pub fn keys(&self) -> Result<Vec<Vec<u8>>> {
+        let files = try!(path_cache(&self.path));
+        let mut keys: Vec<Vec<u8>> = files.keys().map(|k| k.clone()).collect();
+        keys.sort();
+        if self.policy != CachePolicy::Disabled {
+            *self.cache.write().unwrap() = PathCache::new(files);
+        }
+        Ok(keys)
+    }
+
+    /// Forget what's been seen if the policy says it's too old.
+    fn check_cache(&self) {
+        if let CachePolicy::Ttl(ttl) = self.policy {
+            let age = time::precise_time_ns() - self.cache.read().unwrap().since_ns;
+            if Duration::nanoseconds(age as i64) >= ttl {
+                *self.cache.write().unwrap() = PathCache::new(HashMap::new());
+            }
+        }
+    }
+
+    /// Whether the directory has a file called `key`.
+    pub fn has_key(&self, key: &[u8]) -> bool {
+        self.find_key(key).is_ok()
+    }
+
+    /// Create a child cgroup called `name` and return a handle to it.
+    ///
+    /// The kernel populates the control files of a new cgroup as part of the mkdir, but this
+    /// waits (up to a second) for `cgroup.procs` to show up before returning, so the handle
+    /// finds every file.
+    pub fn create_child(&self, name: &str) -> Result<Controller> {
+        let p = self.path.join(name);
+        if name.is_empty() || name == "." || name == ".." || name.contains_char('/') {
+            return Err(Error::InvalidValue(p, "invalid cgroup name".to_string()));
+        }
+
+        try!(sys::mkdir(&p, 0o755));
+
+        let procs = p.join("cgroup.procs");
+        let mut waited = 0;
+        while !procs.exists() && waited < 1000 {
+            timer::sleep(Duration::milliseconds(1));
+            waited += 1;
+        }
+
+        Controller::at(p, self.version)
+    }
+
+    /// Get a fresh handle for the same cgroup.
+    pub fn reopen(&self) -> Result<Controller> {
+        Controller::at(self.path.clone(), self.version).map(|c| c.with_cache_policy(self.policy))
+    }
+
+    /// The directory of this controller's cgroup.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Which hierarchy this controller lives in.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Get a value for a key in this controller, failing with `KeyNotFound` if the key doesn't
+    /// exist
+    pub fn get(&self, key: &[u8]) -> Result<String> {
+        let p = try!(self.find_key(key));
+        File::open(&p).read_to_string().map_err(|e| Error::from_io(&p, e))
+    }
+
+    /// Get the values of several keys at once, each with its own result, as `get` would give.
+    /// The keys already seen are looked up together, under one lock of the cache, and the new
+    /// ones found are remembered under another.
+    pub fn get_many(&self, keys: &[&[u8]]) -> HashMap<Vec<u8>, Result<String>> {
+        let known: Vec<Option<Path>> = if self.policy == CachePolicy::Disabled {
+            keys.iter().map(|_| None).collect()
+        } else {
+            self.check_cache();
+            let cache = self.cache.read().unwrap();
+            keys.iter().map(|&k| cache.files.get(k).map(|p| p.clone())).collect()
+        };
+
+        let mut values = HashMap::new();
+        let mut found = Vec::new();
+        for (&key, known) in keys.iter().zip(known.into_iter()) {
+            let p = match known {
+                Some(p) => p,
+                None => {
+                    let p = self.path.join(key);
+                    if !p.is_file() {
+                        values.insert(key.to_vec(), Err(Error::KeyNotFound(p)));
+                        continue;
+                    }
+                    found.push((key.to_vec(), p.clone()));
+                    p
+                },
+            };
+            let value = File::open(&p).read_to_string().map_err(|e| Error::from_io(&p, e));
+            values.insert(key.to_vec(), value);
+        }
+
+        if self.policy != CachePolicy::Disabled && !found.is_empty() {
+            self.cache.write().unwrap().files.extend(found.into_iter());
+        }
+        values
+    }
+
+    /// Get the lines of a key holding a list, like `devices.list`, leaving out empty ones.
+    pub fn get_lines(&self, key: &[u8]) -> Result<Vec<String>> {
+        let s = try!(self.get(key));
+        Ok(s.as_slice().lines().filter(|l| !l.trim().is_empty()).map(|l| l.to_string()).collect())
+    }
+
+    /// Get a list of pids or tids, one per line like `cgroup.procs`, in ascending order and
+    /// without duplicates. Zeros, for processes outside this process's pid namespace, are left
+    /// out.
+    pub fn get_pids(&self, key: &[u8]) -> Result<Vec<libc::pid_t>> {
+        self.get(key).map(|s| parse_ids(s.as_slice()))
+    }
+
+    /// Get the raw contents of a key, for files that needn't hold UTF-8, failing with
+    /// `KeyNotFound` like `get`.
+    pub fn get_bytes(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let p = try!(self.find_key(key));
+        File::open(&p).read_to_end().map_err(|e| Error::from_io(&p, e))
+    }
+
+    /// The path of a key, from the cache when it's been seen.
+    fn key_path(&self, key: &[u8]) -> Path {
+        self.known_path(key).unwrap_or_else(|| self.path.join(key))
+    }
+
+    /// The path of a key seen before, when the policy remembers it.
+    fn known_path(&self, key: &[u8]) -> Option<Path> {
+        if self.policy == CachePolicy::Disabled {
             return None;
         }
+        self.check_cache();
+        self.cache.read().unwrap().files.get(key).map(|p| p.clone())
+    }
+
+    /// The path of a key that exists, failing with `KeyNotFound` otherwise. A key not seen
+    /// before is looked for in the directory, and remembered if it's there.
+    fn find_key(&self, key: &[u8]) -> Result<Path> {
+        if let Some(p) = self.known_path(key) {
+            return Ok(p);
+        }
+        let p = self.path.join(key);
+        if !p.is_file() {
+            return Err(Error::KeyNotFound(p));
+        }
+        if self.policy != CachePolicy::Disabled {
+            self.cache.write().unwrap().files.insert(key.to_vec(), p.clone());
+        }
+        Ok(p)
+    }
+
+    /// Get a value for a key as an unsigned integer.
+    pub fn get_u64(&self, key: &[u8]) -> Result<u64> {
+        self.get_parsed(key)
+    }
+
+    /// Get a value for a key as a signed integer, for files that use -1 to mean "unlimited".
+    pub fn get_i64(&self, key: &[u8]) -> Result<i64> {
+        self.get_parsed(key)
+    }
+
+    /// Get a value for a key stored as 0 or 1.
+    pub fn get_bool(&self, key: &[u8]) -> Result<bool> {
+        match try!(self.get_parsed::<u8>(key)) {
+            0 => Ok(false),
+            1 => Ok(true),
+            v => Err(self.invalid_value(key, format!("{}", v).as_slice())),
+        }
+    }
 
+    fn get_parsed<T: FromStr>(&self, key: &[u8]) -> Result<T> {
+        let s = try!(self.get(key));
+        match s.as_slice().trim().parse() {
+            Some(v) => Ok(v),
+            None => Err(self.invalid_value(key, s.as_slice())),
+        }
+    }
 
-        Some(File::open(p).read_to_string())
+    /// Get a number-or-`max` value.
+    fn get_limit(&self, key: &[u8]) -> Result<Limit> {
+        let s = try!(self.get(key));
+        match Limit::parse(s.as_slice()) {
+            Some(l) => Ok(l),
+            None => Err(self.invalid_value(key, s.as_slice())),
+        }
+    }
+
+    /// Get the contents of a flat-keyed file, such as `memory.stat` or `cpu.stat`, as a map.
+    pub fn get_flat_keyed(&self, key: &[u8]) -> Result<HashMap<String, u64>> {
+        let s = try!(self.get(key));
+        match parse::flat_keyed(s.as_slice()) {
+            Some(map) => Ok(map),
+            None => Err(self.invalid_value(key, s.as_slice())),
+        }
     }
+
+    /// Get the contents of a flat-keyed file as a type that knows its keys.
+    pub fn get_flat_keyed_as<T: FlatKeyed>(&self, key: &[u8]) -> Result<T> {
+        self.get_flat_keyed(key).map(|map| FlatKeyed::from_map(&map))
+    }
+
+    /// Get the contents of a nested-keyed file, such as `io.stat` or
+    /// `blkio.throttle.io_service_bytes`, as a map from device to its fields.
+    pub fn get_nested_keyed(&self, key: &[u8]) -> Result<HashMap<Device, HashMap<String, u64>>> {
+        let s = try!(self.get(key));
+        match parse::nested_keyed(s.as_slice()) {
+            Some(map) => Ok(map),
+            None => Err(self.invalid_value(key, s.as_slice())),
+        }
+    }
+
+    /// The error for a control file whose contents couldn't be parsed.
+    fn invalid_value(&self, key: &[u8], contents: &str) -> Error {
+        Error::InvalidValue(self.path.join(key), format!("{:?}", contents.trim()))
+    }
+
+    /// Fail with `KeyNotFound` unless this controller lives in the given hierarchy, for files
+    /// only one version has.
+    fn require_version(&self, version: Version, key: &[u8]) -> Result<()> {
+        if self.version == version {
+            Ok(())
+        } else {
+            Err(Error::KeyNotFound(self.path.join(key)))
+        }
+    }
+
+    /// Set the value of a key in this controller.
+    ///
+    /// The value is handed to the kernel in a single write, since each write to a control file is
+    /// parsed on its own. Errors keep the kernel's meaning: a rejected value (EINVAL) comes back
+    /// as `InvalidValue`, missing privileges (EPERM/EACCES) as `PermissionDenied`.
+    pub fn set(&self, key: &[u8], value: &str) -> Result<()> {
+        self.set_bytes(key, value.as_bytes())
+    }
+
+    /// Set the value of a key in this controller from raw bytes, in a single write like `set`,
+    /// so whatever `get_bytes` read can be written back as it was.
+    pub fn set_bytes(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        write_file(&self.path.join(key), value)
+    }
+
+    /// Set several keys, all or nothing: if a write fails, the keys already written are put back
+    /// to the values they had before, in reverse order, and the failure is returned.
+    ///
+    /// Only for files that accept what they read back, like limits and weights. Restoring is
+    /// best-effort, since it can fail for the same reasons the write did.
+    pub fn apply(&self, settings: &[(&[u8], &str)]) -> Result<()> {
+        let mut original = Vec::with_capacity(settings.len());
+        for &(key, _) in settings.iter() {
+            original.push(try!(self.get(key)));
+        }
+
+        for (i, &(key, value)) in settings.iter().enumerate() {
+            if let Err(e) = self.set(key, value) {
+                for j in range(0, i).rev() {
+                    // files like io.max list one entry per line, but take one per write
+                    for line in original[j].as_slice().lines().filter(|l| !l.is_empty()) {
+                        let _ = self.set(settings[j].0, line);
+                    }
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write several keys in order, reporting how each went. With `stop_on_error` the writes
+    /// stop at the first failure and the rest are reported as skipped; nothing is put back, as
+    /// `apply` would.
+    pub fn set_many(&self, settings: &[(&[u8], &str)], stop_on_error: bool) -> SetReport {
+        let mut report = SetReport { results: Vec::new(), skipped: Vec::new() };
+        for (i, &(key, value)) in settings.iter().enumerate() {
+            let result = self.set(key, value);
+            let failed = result.is_err();
+            report.results.push((key.to_vec(), result));
+            if failed && stop_on_error {
+                report.skipped.extend(settings[i + 1..].iter().map(|&(k, _)| k.to_vec()));
+                break;
+            }
+        }
+        report
+    }
+
+    /// Move a process, with all of its threads, into this cgroup.
+    pub fn add_pid(&self, pid: libc::pid_t) -> Result<()> {
+        self.write_id(b"cgroup.procs", pid)
+    }
+
+    /// Move a single thread into this cgroup through the v1 `tasks` file.
+    pub fn add_task(&self, tid: libc::pid_t) -> Result<()> {
+        try!(self.require_version(Version::V1, b"tasks"));
+        self.write_id(b"tasks", tid)
+    }
+
+    /// The processes in this cgroup, from `cgroup.procs`, in ascending order.
+    ///
+    /// The list is a moment's picture: processes may exit or move right after, so signalling
+    /// them has to allow for ESRCH. Processes outside this process's pid namespace, which the
+    /// kernel shows as 0, are left out. Threaded v2 cgroups can't list theirs, and the kernel
+    /// refuses the read.
+    pub fn procs(&self) -> Result<Vec<libc::pid_t>> {
+        self.get_pids(b"cgroup.procs")
+    }
+
+    /// The threads in this cgroup, from the v1 `tasks`, like `procs`.
+    pub fn tasks(&self) -> Result<Vec<libc::pid_t>> {
+        try!(self.require_version(Version::V1, b"tasks"));
+        self.get_pids(b"tasks")
+    }
+
+    /// Write a pid or tid to a file. The kernel's answer comes back as `InvalidValue` for a
+    /// process that doesn't exist (ESRCH), and `Busy` for a v2 cgroup that has controllers
+    /// enabled for its children (EBUSY), since processes can only live in the leaves.
+    fn write_id(&self, key: &[u8], id: libc::pid_t) -> Result<()> {
+        if id <= 0 {
+            return Err(Error::InvalidValue(self.path.join(key), format!("invalid id {}", id)));
+        }
+        self.set(key, format!("{}", id).as_slice())
+    }
+
+    /// Kill every process in this cgroup and its descendants.
+    ///
+    /// Kernels with `cgroup.kill` (v2, 5.14 and later) do this atomically. Otherwise every pid
+    /// in `cgroup.procs` across the subtree is sent SIGKILL, repeatedly until none are left, so
+    /// processes forked in the meantime are caught too. If there are still some after 100
+    /// rounds this gives up with `Busy`, rather than leave `remove` to fail.
+    pub fn kill_all(&self) -> Result<()> {
+        if self.path.join("cgroup.kill").is_file() {
+            return self.set(b"cgroup.kill", "1");
+        }
+
+        for _ in range(0, 100) {
+            if try!(kill_tree(&self.path)) == 0 {
+                return Ok(());
+            }
+            timer::sleep(Duration::milliseconds(1));
+        }
+        // the last round has had its sleep to take effect
+        if try!(self.procs_recursive()).is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Busy(self.path.clone()))
+        }
+    }
+
+    /// Remove this cgroup.
+    ///
+    /// The kernel refuses with `Busy` while it has child cgroups or member processes.
+    pub fn remove(self) -> Result<()> {
+        sys::rmdir(&self.path)
+    }
+
+    /// Remove this cgroup and every cgroup beneath it, leaves first.
+    pub fn remove_recursive(self, mode: RemoveMode) -> Result<()> {
+        let target = match mode {
+            RemoveMode::Strict => None,
+            RemoveMode::MigrateToParent => Some(self.path.dir_path().join("cgroup.procs")),
+        };
+        remove_tree(&self.path, target.as_ref())
+    }
+}
+
+/// Read a whole file whose path isn't behind a controller's cache.
+fn read_file(path: &Path) -> Result<String> {
+    File::open(path).read_to_string().map_err(|e| Error::from_io(path, e))
+}
+
+/// Read a list of pids or tids, one per line, skipping zeros and duplicates.
+fn read_ids(path: &Path) -> Result<Vec<libc::pid_t>> {
+    read_file(path).map(|contents| parse_ids(contents.as_slice()))
+}
+
+/// Read the processes of a cgroup directory, from `cgroup.procs`. Threaded v2 cgroups refuse
+/// to list theirs (EOPNOTSUPP) and are taken to have none: every process with threads in one
+/// is listed at the root of its threaded subtree.
+fn read_procs(dir: &Path) -> Result<Vec<libc::pid_t>> {
+    match read_ids(&dir.join("cgroup.procs")) {
+        Err(Error::Io(..)) if is_threaded(dir) => Ok(Vec::new()),
+        r => r,
+    }
+}
+
+/// Whether a cgroup directory is a threaded v2 cgroup.
+fn is_threaded(dir: &Path) -> bool {
+    match read_file(&dir.join("cgroup.type")) {
+        Ok(t) => t.as_slice().trim() == "threaded",
+        Err(_) => false,
+    }
+}
+
+/// Parse a list of pids or tids like `read_ids`.
+fn parse_ids(contents: &str) -> Vec<libc::pid_t> {
+    let mut ids: Vec<libc::pid_t> = contents.lines()
+        .filter_map(|l| l.trim().parse())
+        .filter(|&id| id > 0)
+        .collect();
+    // cgroup.procs can list a process more than once while its threads are moving
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// List a cgroup directory.
+fn read_dir(dir: &Path) -> Result<Vec<Path>> {
+    fs::readdir(dir).map_err(|e| Error::from_io(dir, e))
+}
+
+/// Remove a cgroup directory from the leaves up, moving members to `target` first if given.
+fn remove_tree(dir: &Path, target: Option<&Path>) -> Result<()> {
+    for child in try!(read_dir(dir)).into_iter() {
+        if child.is_dir() {
+            try!(remove_tree(&child, target));
+        }
+    }
+
+    if let Some(target) = target {
+        let procs = try!(read_file(&dir.join("cgroup.procs")));
+        for pid in procs.as_slice().lines() {
+            // Processes may exit while we go; whatever is left makes the rmdir fail below.
+            let _ = write_file(target, pid.as_bytes());
+        }
+    }
+
+    sys::rmdir(dir)
+}
+
+/// SIGKILL every process in a cgroup directory and those beneath it, returning how many were
+/// signalled.
+fn kill_tree(dir: &Path) -> Result<usize> {
+    let mut killed = 0;
+    for child in try!(read_dir(dir)).into_iter() {
+        if child.is_dir() {
+            killed += try!(kill_tree(&child));
+        }
+    }
+
+    for pid in try!(read_procs(dir)).into_iter() {
+        // the process may already be gone
+        unsafe { libc::funcs::posix88::signal::kill(pid, libc::SIGKILL); }
+        killed += 1;
+    }
+    Ok(killed)
+}
+
+/// Write a value to a control file in one go.
+///
+/// Control files can't be created, so this opens without `O_CREAT` and a missing file is a
+/// `KeyNotFound`.
+fn write_file(path: &Path, value: &[u8]) -> Result<()> {
+    let fd = try!(sys::Fd::open(path, libc::O_WRONLY | libc::O_TRUNC));
+    fd.write(value)
 }
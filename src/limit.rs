@@ -0,0 +1,68 @@
+//! Values that are either a number or unlimited.
+
+use std::default::Default;
+use std::fmt;
+use std::str::FromStr;
+
+/// A limit that may be unset, which the kernel writes as `max` (or in v1 as -1).
+///
+/// Limits compare the way they constrain, with `Max` above every value.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Limit {
+    /// Limited to this value
+    Value(u64),
+    /// No limit
+    Max,
+}
+
+impl Limit {
+    /// Parse the kernel's encoding: a number, or `max` or -1.
+    pub fn parse(s: &str) -> Option<Limit> {
+        match s.trim() {
+            "max" | "-1" => Some(Limit::Max),
+            s => s.parse().map(Limit::Value),
+        }
+    }
+
+    /// The limit as a number, None if unlimited.
+    pub fn value(&self) -> Option<u64> {
+        match *self {
+            Limit::Max => None,
+            Limit::Value(v) => Some(v),
+        }
+    }
+
+    pub fn is_max(&self) -> bool {
+        *self == Limit::Max
+    }
+
+    /// How v1 files write the limit, with -1 for unlimited.
+    pub fn to_v1_string(&self) -> String {
+        match *self {
+            Limit::Max => "-1".to_string(),
+            Limit::Value(v) => v.to_string(),
+        }
+    }
+}
+
+impl FromStr for Limit {
+    fn from_str(s: &str) -> Option<Limit> {
+        Limit::parse(s)
+    }
+}
+
+impl Default for Limit {
+    fn default() -> Limit {
+        Limit::Max
+    }
+}
+
+impl fmt::String for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Limit::Max => write!(f, "max"),
+            Limit::Value(v) => write!(f, "{}", v),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! The memory controller.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::IoErrorKind;
+use std::io::fs::PathExtensions;
+use std::os;
+
+use {Controller, Error, Limit, Result, Version};
+use parse;
+use system;
+
+flat_keyed! {
+    /// Counters from the v2 `memory.events`.
+    pub struct MemoryEvents {
+        /// Times usage went below `memory.low` under reclaim pressure
+        low,
+        /// Times usage went over `memory.high` and the group was throttled
+        high,
+        /// Times usage was about to go over `memory.max`
+        max,
+        /// Times the group hit its limit and allocations failed
+        oom,
+        /// Processes killed by the OOM killer
+        oom_kill,
+        /// Times the whole group was OOM killed
+        oom_group_kill
+    }
+}
+
+/// The v2 memory protection and limit tiers, from the most protected to the hardest limit.
+/// Tiers left as None aren't touched.
+#[derive(Clone, Copy, PartialEq, Eq, Show, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MemoryProtection {
+    /// `memory.min`: memory that's never reclaimed
+    pub min: Option<Limit>,
+    /// `memory.low`: memory only reclaimed when nothing else is left
+    pub low: Option<Limit>,
+    /// `memory.high`: usage over this is throttled and reclaimed hard
+    pub high: Option<Limit>,
+    /// `memory.max`: usage over this triggers the OOM killer
+    pub max: Option<Limit>,
+}
+
+/// Typed access to the memory controller, hiding the file name differences between v1 and v2.
+pub struct MemoryController {
+    inner: Controller,
+}
+
+impl MemoryController {
+    /// Wrap a controller handle for the memory controller.
+    pub fn new(inner: Controller) -> MemoryController {
+        MemoryController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    fn key(&self, v1: &'static str, v2: &'static str) -> &'static [u8] {
+        match self.inner.version() {
+            Version::V1 => v1.as_bytes(),
+            Version::V2 => v2.as_bytes(),
+        }
+    }
+
+    /// Current memory usage in bytes.
+    pub fn usage(&self) -> Result<u64> {
+        self.inner.get_u64(self.key("memory.usage_in_bytes", "memory.current"))
+    }
+
+    /// The hard memory limit in bytes.
+    pub fn limit(&self) -> Result<Limit> {
+        match self.inner.version() {
+            Version::V1 => self.v1_limit(b"memory.limit_in_bytes"),
+            Version::V2 => self.inner.get_limit(b"memory.max"),
+        }
+    }
+
+    /// The limit that actually binds this group: the smallest hard limit of the group and its
+    /// ancestors, since a parent's limit covers everything below it.
+    pub fn effective_limit(&self) -> Result<Limit> {
+        let mut limit = try!(self.limit());
+        for ancestor in try!(self.inner.ancestors()).into_iter() {
+            match MemoryController::new(ancestor).limit() {
+                Ok(l) => if l < limit { limit = l },
+                // the v2 root has no limit
+                Err(Error::KeyNotFound(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(limit)
+    }
+
+    /// Read a v1 limit in bytes.
+    fn v1_limit(&self, key: &[u8]) -> Result<Limit> {
+        // v1 reports "unlimited" as the largest page-aligned i64
+        let unlimited = ::std::i64::MAX as u64 & !(os::page_size() as u64 - 1);
+        let v = try!(self.inner.get_u64(key));
+        Ok(if v >= unlimited { Limit::Max } else { Limit::Value(v) })
+    }
+
+    /// Set the hard memory limit in bytes.
+    pub fn set_limit(&self, limit: Limit) -> Result<()> {
+        let value = match self.inner.version() {
+            Version::V1 => limit.to_v1_string(),
+            Version::V2 => limit.to_string(),
+        };
+        self.inner.set(self.key("memory.limit_in_bytes", "memory.max"), value.as_slice())
+    }
+
+    /// Set the hard memory limit from a size like `512M`, or `max` to remove it.
+    pub fn set_limit_str(&self, size: &str) -> Result<()> {
+        self.inner.set_size(self.key("memory.limit_in_bytes", "memory.max"), size)
+    }
+
+    /// Set the v2 protection and limit tiers together, all or nothing as with
+    /// `Controller::apply`.
+    ///
+    /// The tiers given must be in order, `min` ≤ `low` ≤ `high` ≤ `max`, or nothing is written
+    /// and the first pair out of order is `InvalidValue`. Tiers left out aren't checked against
+    /// the ones already set.
+    pub fn set_protection(&self, p: MemoryProtection) -> Result<()> {
+        try!(self.inner.require_version(Version::V2, b"memory.min"));
+        let tiers = [("memory.min", p.min), ("memory.low", p.low), ("memory.high", p.high),
+                     ("memory.max", p.max)];
+        let given: Vec<(&str, Limit)> = tiers.iter().filter_map(|&(k, l)| l.map(|l| (k, l)))
+                                             .collect();
+        for pair in given.windows(2) {
+            let ((lower_key, lower), (upper_key, upper)) = (pair[0], pair[1]);
+            if lower > upper {
+                return Err(Error::InvalidValue(self.inner.path().join(lower_key), format!(
+                    "{} {} is over {} {}", lower_key, lower, upper_key, upper)));
+            }
+        }
+
+        let values: Vec<(&[u8], String)> = given.iter()
+                                                .map(|&(k, l)| (k.as_bytes(), l.to_string()))
+                                                .collect();
+        let settings: Vec<(&[u8], &str)> = values.iter().map(|&(k, ref v)| (k, v.as_slice()))
+                                                 .collect();
+        self.inner.apply(settings.as_slice())
+    }
+
+    /// Set the hard memory limit to a percentage of the machine's memory, such as 25.0,
+    /// rounded down to whole pages.
+    pub fn set_limit_percent(&self, percent: f64) -> Result<()> {
+        if !(percent > 0.0 && percent <= 100.0) {
+            let key = self.key("memory.limit_in_bytes", "memory.max");
+            return Err(Error::InvalidValue(self.inner.path().join(key),
+                                           format!("{} is not a percentage", percent)));
+        }
+        let page = os::page_size() as u64;
+        let bytes = (try!(system::memory_total()) as f64 * percent / 100.0) as u64;
+        self.set_limit(Limit::Value(bytes / page * page))
+    }
+
+    /// The contents of `memory.stat`. The keys differ between v1 and v2.
+    pub fn stat(&self) -> Result<HashMap<String, u64>> {
+        self.inner.get_flat_keyed(b"memory.stat")
+    }
+
+    /// Memory use by NUMA node from `memory.numa_stat`: for each category (like `anon` or
+    /// `file`), bytes by node number. v1 counts pages, which are turned into bytes here.
+    pub fn numa_stat(&self) -> Result<HashMap<String, BTreeMap<u32, u64>>> {
+        let s = try!(self.inner.get(b"memory.numa_stat"));
+        let mut stat = match parse::numa_stat(s.as_slice()) {
+            Some(stat) => stat,
+            None => return Err(self.inner.invalid_value(b"memory.numa_stat", s.as_slice())),
+        };
+        if self.inner.version() == Version::V1 {
+            let page = os::page_size() as u64;
+            for nodes in stat.values_mut() {
+                for v in nodes.values_mut() {
+                    *v *= page;
+                }
+            }
+        }
+        Ok(stat)
+    }
+
+    /// Current swap usage in bytes.
+    pub fn swap_usage(&self) -> Result<u64> {
+        match self.inner.version() {
+            Version::V1 => {
+                // memsw counts memory and swap together
+                let memsw = try!(self.inner.get_u64(b"memory.memsw.usage_in_bytes"));
+                let mem = try!(self.usage());
+                Ok(if memsw > mem { memsw - mem } else { 0 })
+            },
+            Version::V2 => self.inner.get_u64(b"memory.swap.current"),
+        }
+    }
+
+    /// The v2 limit on swap usage, from `memory.swap.max`.
+    pub fn swap_max(&self) -> Result<Limit> {
+        self.inner.get_limit(b"memory.swap.max")
+    }
+
+    /// Set the v2 limit on swap usage; the group can't swap out past it.
+    pub fn set_swap_max(&self, max: Limit) -> Result<()> {
+        self.inner.set(b"memory.swap.max", max.to_string().as_slice())
+    }
+
+    /// The v2 swap usage at which the group is throttled, from `memory.swap.high`.
+    pub fn swap_high(&self) -> Result<Limit> {
+        self.inner.get_limit(b"memory.swap.high")
+    }
+
+    /// Set the v2 swap usage at which the group is throttled.
+    pub fn set_swap_high(&self, high: Limit) -> Result<()> {
+        self.inner.set(b"memory.swap.high", high.to_string().as_slice())
+    }
+
+    /// The v1 limit on memory and swap together, from `memory.memsw.limit_in_bytes`.
+    pub fn memsw_limit(&self) -> Result<Limit> {
+        try!(self.inner.require_version(Version::V1, b"memory.memsw.limit_in_bytes"));
+        self.v1_limit(b"memory.memsw.limit_in_bytes")
+    }
+
+    /// Set the v1 memory and memory-plus-swap limits together.
+    ///
+    /// v1 wants the memory-plus-swap limit to be at least the memory limit at all times, so a
+    /// smaller one is `InvalidValue`, and the two are written in whichever order keeps it so.
+    pub fn set_limit_with_swap(&self, limit: Limit, memsw: Limit) -> Result<()> {
+        let key = b"memory.memsw.limit_in_bytes";
+        try!(self.inner.require_version(Version::V1, key));
+        if limit > memsw {
+            return Err(Error::InvalidValue(self.inner.path().join(key), format!(
+                "memory and swap limit {} is below the memory limit {}", memsw, limit)));
+        }
+
+        let write_memsw = |&: | self.inner.set(key, memsw.to_v1_string().as_slice());
+        // raising the memory limit past the current memsw limit would be refused
+        if limit > try!(self.memsw_limit()) {
+            try!(write_memsw());
+            self.set_limit(limit)
+        } else {
+            try!(self.set_limit(limit));
+            write_memsw()
+        }
+    }
+
+    /// Ask the kernel to reclaim `bytes` of this group's memory now, through the v2
+    /// `memory.reclaim` (5.19 and later). False if it gave up with less reclaimed (EAGAIN),
+    /// which is normal for groups with little left to give back.
+    pub fn reclaim(&self, bytes: u64) -> Result<bool> {
+        match self.inner.set(b"memory.reclaim", bytes.to_string().as_slice()) {
+            Ok(()) => Ok(true),
+            Err(Error::Io(_, ref e)) if e.kind == IoErrorKind::ResourceUnavailable => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether the kernel can OOM kill groups as a whole (v2, 4.19 and later). Like zswap, the
+    /// root cgroup doesn't have the file.
+    pub fn has_oom_group(&self) -> bool {
+        self.inner.path().join("memory.oom.group").exists()
+    }
+
+    /// Whether an OOM kill in this group kills every process in it, from `memory.oom.group`.
+    pub fn oom_group(&self) -> Result<bool> {
+        self.inner.get_bool(b"memory.oom.group")
+    }
+
+    /// Make an OOM kill in this group kill all of it rather than one process, for workloads
+    /// that can't run with a part missing. Processes with `oom_score_adj` at -1000 are spared.
+    pub fn set_oom_group(&self, group: bool) -> Result<()> {
+        self.inner.set(b"memory.oom.group", if group { "1" } else { "0" })
+    }
+
+    /// Whether the kernel has zswap limits for cgroups (v2, 5.19 and later). The root
+    /// cgroup has no zswap files, so it's always false there.
+    pub fn has_zswap(&self) -> bool {
+        self.inner.path().join("memory.zswap.max").exists()
+    }
+
+    /// Whether `memory.zswap.writeback` can be set (6.8 and later).
+    pub fn has_zswap_writeback(&self) -> bool {
+        self.inner.path().join("memory.zswap.writeback").exists()
+    }
+
+    /// Memory taken by zswap for this group's compressed pages, in bytes.
+    pub fn zswap_usage(&self) -> Result<u64> {
+        self.inner.get_u64(b"memory.zswap.current")
+    }
+
+    /// The limit on memory taken by zswap, from `memory.zswap.max`.
+    pub fn zswap_max(&self) -> Result<Limit> {
+        self.inner.get_limit(b"memory.zswap.max")
+    }
+
+    /// Limit the memory zswap may take for this group; pages past it go to swap uncompressed.
+    pub fn set_zswap_max(&self, max: Limit) -> Result<()> {
+        self.inner.set(b"memory.zswap.max", max.to_string().as_slice())
+    }
+
+    /// Whether pages may be written back from zswap to swap.
+    pub fn zswap_writeback(&self) -> Result<bool> {
+        self.inner.get_bool(b"memory.zswap.writeback")
+    }
+
+    /// Allow or forbid writing pages back from zswap to swap.
+    pub fn set_zswap_writeback(&self, writeback: bool) -> Result<()> {
+        self.inner.set(b"memory.zswap.writeback", if writeback { "1" } else { "0" })
+    }
+
+    /// The v2 `memory.events` counters.
+    pub fn events(&self) -> Result<MemoryEvents> {
+        self.inner.get_flat_keyed_as(b"memory.events")
+    }
+
+    /// How many times usage hit the limit.
+    ///
+    /// v2 has no `failcnt`, so this is the `max` counter of `memory.events` there.
+    pub fn failcnt(&self) -> Result<u64> {
+        match self.inner.version() {
+            Version::V1 => self.inner.get_u64(b"memory.failcnt"),
+            Version::V2 => {
+                let events = try!(self.inner.get_flat_keyed(b"memory.events"));
+                Ok(events.get("max").map(|&v| v).unwrap_or(0))
+            },
+        }
+    }
+}
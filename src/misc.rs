@@ -0,0 +1,67 @@
+//! The v2 misc controller, for scalar resources like SEV ASIDs.
+
+use std::collections::HashMap;
+
+use {Controller, Limit, Result};
+
+/// Typed access to the misc controller, keyed by resource name (`sev`, `sev_es`, `tdx`, ...).
+pub struct MiscController {
+    inner: Controller,
+}
+
+impl MiscController {
+    /// Wrap a controller handle for the misc controller.
+    pub fn new(inner: Controller) -> MiscController {
+        MiscController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// How much of each resource the host has. Only the root cgroup has `misc.capacity`.
+    pub fn capacity(&self) -> Result<HashMap<String, u64>> {
+        self.inner.get_flat_keyed(b"misc.capacity")
+    }
+
+    /// How much of each resource the group is using.
+    pub fn current(&self) -> Result<HashMap<String, u64>> {
+        self.inner.get_flat_keyed(b"misc.current")
+    }
+
+    /// The limit on each resource.
+    pub fn max(&self) -> Result<HashMap<String, Limit>> {
+        let s = try!(self.inner.get(b"misc.max"));
+        let mut map = HashMap::new();
+        for line in s.as_slice().lines() {
+            let words: Vec<&str> = line.words().collect();
+            if words.is_empty() {
+                continue;
+            }
+            match (words.len(), Limit::parse(words.last().map(|w| *w).unwrap_or(""))) {
+                (2, Some(l)) => { map.insert(words[0].to_string(), l); },
+                _ => return Err(self.inner.invalid_value(b"misc.max", s.as_slice())),
+            }
+        }
+        Ok(map)
+    }
+
+    /// Set the limit on one resource.
+    pub fn set_max(&self, resource: &str, max: Limit) -> Result<()> {
+        self.inner.set(b"misc.max", format!("{} {}", resource, max).as_slice())
+    }
+
+    /// How many times each resource was about to go over its limit, from `misc.events`.
+    pub fn events(&self) -> Result<HashMap<String, u64>> {
+        let map = try!(self.inner.get_flat_keyed(b"misc.events"));
+        // the keys are "<resource>.max"
+        Ok(map.into_iter().map(|(k, v)| {
+            let resource = match k.as_slice().rfind('.') {
+                Some(i) => k.as_slice()[..i].to_string(),
+                None => k.clone(),
+            };
+            (resource, v)
+        }).collect())
+    }
+}
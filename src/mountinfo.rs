@@ -0,0 +1,250 @@
+//! Finding cgroup filesystems in `/proc/self/mountinfo`.
+
+use std::io::fs::PathExtensions;
+
+use Result;
+use sys;
+
+/// Mount super options of cgroup v1 hierarchies that aren't controller names.
+static NON_CONTROLLER_OPTIONS: &'static [&'static str] = &[
+    "rw", "ro", "xattr", "noprefix", "clone_children", "cpuset_v2_mode", "favordynmods",
+];
+
+/// One line of mountinfo.
+#[derive(Clone, Show)]
+pub struct Mount {
+    pub mount_id: u32,
+    pub parent_id: u32,
+    /// The directory of the filesystem that is mounted, `/` unless it's a bind mount of part of
+    /// it (or inside a cgroup namespace)
+    pub root: Path,
+    /// Where it's mounted
+    pub mount_point: Path,
+    /// Per-mount options, like `rw,nosuid`
+    pub options: Vec<String>,
+    pub fs_type: String,
+    pub source: String,
+    /// Per-filesystem options; for cgroup v1 these name the controllers of the hierarchy
+    pub super_options: Vec<String>,
+}
+
+impl Mount {
+    /// Whether this is a cgroup v1 hierarchy.
+    pub fn is_cgroup(&self) -> bool {
+        self.fs_type.as_slice() == "cgroup"
+    }
+
+    /// Whether this is the unified hierarchy.
+    pub fn is_cgroup2(&self) -> bool {
+        self.fs_type.as_slice() == "cgroup2"
+    }
+
+    /// Whether the unified hierarchy is mounted with `nsdelegate`, making cgroup namespaces
+    /// delegation boundaries: a process can't move processes across its namespace's root, or
+    /// write the files of that root.
+    pub fn nsdelegate(&self) -> bool {
+        self.is_cgroup2() && self.super_options.iter().any(|o| o.as_slice() == "nsdelegate")
+    }
+
+    /// Whether the unified hierarchy is mounted with `memory_recursiveprot`, passing the
+    /// `memory.min` and `memory.low` protection of a cgroup on to descendants that don't claim
+    /// it themselves.
+    pub fn memory_recursiveprot(&self) -> bool {
+        self.is_cgroup2() &&
+            self.super_options.iter().any(|o| o.as_slice() == "memory_recursiveprot")
+    }
+
+    /// The controllers of a cgroup v1 hierarchy, in the order the kernel lists them.
+    pub fn controllers(&self) -> Vec<Vec<u8>> {
+        if !self.is_cgroup() {
+            return Vec::new();
+        }
+        self.super_options.iter()
+            .filter(|o| !NON_CONTROLLER_OPTIONS.contains(&o.as_slice()))
+            .filter(|o| !o.as_slice().starts_with("release_agent=") &&
+                        !o.as_slice().starts_with("name="))
+            .map(|o| o.as_bytes().to_vec())
+            .collect()
+    }
+
+    /// The name given to a cgroup v1 hierarchy with `name=`, like `systemd`.
+    pub fn named(&self) -> Option<String> {
+        if !self.is_cgroup() {
+            return None;
+        }
+        self.super_options.iter().find(|o| o.as_slice().starts_with("name="))
+            .map(|o| o.as_slice()["name=".len()..].to_string())
+    }
+
+    /// The name of a cgroup v1 hierarchy as `/proc/<pid>/cgroup` shows it: its controllers
+    /// joined with commas, like `cpu,cpuacct`, then `name=` and its name if it has one, as in
+    /// `name=systemd`.
+    pub fn hierarchy_name(&self) -> Vec<u8> {
+        let mut parts = self.controllers();
+        if let Some(name) = self.named() {
+            parts.push(format!("name={}", name).into_bytes());
+        }
+        let mut name = Vec::new();
+        for c in parts.iter() {
+            if !name.is_empty() { name.push(b','); }
+            name.push_all(c.as_slice());
+        }
+        name
+    }
+}
+
+/// Undo the octal escaping of spaces, tabs, newlines and backslashes in mountinfo paths.
+fn unescape(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() &&
+           bytes[i+1..i+4].iter().all(|&b| b >= b'0' && b <= b'7') {
+            out.push(bytes[i+1..i+4].iter().fold(0u8, |acc, &b| acc * 8 + (b - b'0')));
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Parse the contents of a mountinfo file. None if any line is malformed.
+pub fn parse(contents: &str) -> Option<Vec<Mount>> {
+    let mut mounts = Vec::new();
+    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.words().collect();
+        // optional fields run up to a lone "-"
+        let sep = match fields.iter().position(|&f| f == "-") {
+            Some(i) if i >= 6 && fields.len() >= i + 3 => i,
+            _ => return None,
+        };
+        let (mount_id, parent_id) = match (fields[0].parse(), fields[1].parse()) {
+            (Some(m), Some(p)) => (m, p),
+            _ => return None,
+        };
+        let split = |&: s: &str| s.split(',').map(|o| o.to_string()).collect();
+        mounts.push(Mount {
+            mount_id: mount_id,
+            parent_id: parent_id,
+            root: Path::new(unescape(fields[3])),
+            mount_point: Path::new(unescape(fields[4])),
+            options: split(fields[5]),
+            fs_type: fields[sep + 1].to_string(),
+            source: fields[sep + 2].to_string(),
+            super_options: if fields.len() > sep + 3 { split(fields[sep + 3]) } else { Vec::new() },
+        });
+    }
+    Some(mounts)
+}
+
+/// The mounts of the current process.
+pub fn mounts() -> Result<Vec<Mount>> {
+    let p = Path::new("/proc/self/mountinfo");
+    let contents = try!(::read_file(&p));
+    match parse(contents.as_slice()) {
+        Some(mounts) => Ok(mounts),
+        None => Err(::Error::InvalidValue(p, "malformed mountinfo".to_string())),
+    }
+}
+
+/// The cgroup filesystems among the mounts of the current process.
+pub fn cgroup_mounts() -> Result<Vec<Mount>> {
+    Ok(try!(mounts()).into_iter().filter(|m| m.is_cgroup() || m.is_cgroup2()).collect())
+}
+
+/// Mount the unified hierarchy at `target`, creating the directory if need be, unless it's
+/// mounted somewhere already. Returns whether it was mounted here. This takes `CAP_SYS_ADMIN`,
+/// and is meant for systems with nothing else to set cgroups up, like an initramfs or a test
+/// VM.
+pub fn mount_unified(target: &Path) -> Result<bool> {
+    if try!(cgroup_mounts()).iter().any(|m| m.is_cgroup2()) {
+        return Ok(false);
+    }
+    try!(mount_cgroup_fs(target, "cgroup2", ""));
+    Ok(true)
+}
+
+/// Mount a v1 hierarchy of `controllers` at `target` like `mount_unified`, unless a hierarchy
+/// of just those controllers is mounted already. The kernel refuses, with `Busy`, controllers
+/// that are bound to another hierarchy or in use in the unified one.
+pub fn mount_controllers(target: &Path, controllers: &[&[u8]]) -> Result<bool> {
+    if controllers.is_empty() {
+        // the kernel would take that as every controller it has
+        return Err(::Error::InvalidValue(target.clone(), "no controllers".to_string()));
+    }
+    let mut wanted: Vec<Vec<u8>> = controllers.iter().map(|c| c.to_vec()).collect();
+    wanted.sort();
+    for m in try!(cgroup_mounts()).iter().filter(|m| m.is_cgroup()) {
+        let mut have = m.controllers();
+        have.sort();
+        if have == wanted {
+            return Ok(false);
+        }
+    }
+    let names: Vec<String> = wanted.iter()
+        .map(|c| String::from_utf8_lossy(c.as_slice()).into_owned()).collect();
+    try!(mount_cgroup_fs(target, "cgroup", names.connect(",").as_slice()));
+    Ok(true)
+}
+
+fn mount_cgroup_fs(target: &Path, fs_type: &str, options: &str) -> Result<()> {
+    if !target.is_dir() {
+        try!(sys::mkdir(target, 0o755));
+    }
+    sys::mount_fs(fs_type, target, fs_type, sys::MS_NOSUID | sys::MS_NODEV | sys::MS_NOEXEC,
+                  options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, unescape};
+
+    #[test]
+    fn unescape_octal() {
+        assert_eq!(unescape("/a\\040b"), b"/a b".to_vec());
+        assert_eq!(unescape("tab\\011nl\\012bs\\134"), b"tab\tnl\nbs\\".to_vec());
+        // not three octal digits, so left alone
+        assert_eq!(unescape("a\\08"), b"a\\08".to_vec());
+        assert_eq!(unescape("a\\04"), b"a\\04".to_vec());
+    }
+
+    #[test]
+    fn parse_optional_fields() {
+        let mounts = parse("\
+25 19 0:22 / /sys/fs/cgroup ro,nosuid - tmpfs tmpfs ro,mode=755
+36 25 0:31 / /cg/cpu,cpuacct rw,relatime shared:12 master:3 - cgroup cgroup rw,cpu,cpuacct
+40 25 0:35 /init.scope /sys/fs/cgroup/my\\040dir rw shared:1 - cgroup2 cgroup2 rw,nsdelegate
+").unwrap();
+        assert_eq!(mounts.len(), 3);
+
+        assert_eq!(mounts[0].fs_type.as_slice(), "tmpfs");
+        assert_eq!(mounts[0].super_options, vec!["ro".to_string(), "mode=755".to_string()]);
+
+        let v1 = &mounts[1];
+        assert_eq!((v1.mount_id, v1.parent_id), (36, 25));
+        assert_eq!(v1.mount_point, Path::new("/cg/cpu,cpuacct"));
+        assert_eq!(v1.options, vec!["rw".to_string(), "relatime".to_string()]);
+        assert_eq!(v1.source.as_slice(), "cgroup");
+        assert!(v1.is_cgroup());
+        assert_eq!(v1.controllers(), vec![b"cpu".to_vec(), b"cpuacct".to_vec()]);
+
+        let v2 = &mounts[2];
+        assert_eq!(v2.root, Path::new("/init.scope"));
+        assert_eq!(v2.mount_point, Path::new("/sys/fs/cgroup/my dir"));
+        assert!(v2.is_cgroup2());
+        assert!(v2.nsdelegate());
+    }
+
+    #[test]
+    fn parse_malformed() {
+        // no separator
+        assert!(parse("25 19 0:22 / /sys/fs/cgroup ro tmpfs tmpfs ro\n").is_none());
+        // nothing after the separator
+        assert!(parse("25 19 0:22 / /sys/fs/cgroup ro -\n").is_none());
+        assert!(parse("x 19 0:22 / /sys/fs/cgroup ro - tmpfs tmpfs ro\n").is_none());
+        assert_eq!(parse("\n\n").unwrap().len(), 0);
+    }
+}
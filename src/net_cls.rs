@@ -0,0 +1,79 @@
+//! The v1 net_cls controller.
+
+use std::fmt;
+use std::num::from_str_radix;
+
+use {Controller, Result};
+
+/// A traffic control class handle, written `major:minor` in hex by `tc`.
+///
+/// The kernel packs it into 32 bits as `0xMMMMmmmm`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClassId {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ClassId {
+    /// Unpack the kernel's 32-bit value.
+    pub fn from_raw(raw: u32) -> ClassId {
+        ClassId { major: (raw >> 16) as u16, minor: raw as u16 }
+    }
+
+    /// Pack into the kernel's 32-bit value.
+    pub fn to_raw(&self) -> u32 {
+        (self.major as u32) << 16 | self.minor as u32
+    }
+
+    /// Parse `tc` notation, like `10:1`.
+    pub fn parse(s: &str) -> Option<ClassId> {
+        let mut parts = s.trim().split(':');
+        let major = match parts.next().and_then(|p| from_str_radix(p, 16)) {
+            Some(m) => m,
+            None => return None,
+        };
+        let minor = match parts.next().and_then(|p| from_str_radix(p, 16)) {
+            Some(m) => m,
+            None => return None,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(ClassId { major: major, minor: minor })
+    }
+}
+
+impl fmt::String for ClassId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:x}:{:x}", self.major, self.minor)
+    }
+}
+
+/// Typed access to the v1 net_cls controller.
+pub struct NetClsController {
+    inner: Controller,
+}
+
+impl NetClsController {
+    /// Wrap a controller handle for the net_cls controller.
+    pub fn new(inner: Controller) -> NetClsController {
+        NetClsController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// The class packets from this group are tagged with.
+    pub fn classid(&self) -> Result<ClassId> {
+        let raw = try!(self.inner.get_u64(b"net_cls.classid"));
+        Ok(ClassId::from_raw(raw as u32))
+    }
+
+    /// Tag packets from this group with a class.
+    pub fn set_classid(&self, id: ClassId) -> Result<()> {
+        self.inner.set(b"net_cls.classid", format!("{}", id.to_raw()).as_slice())
+    }
+}
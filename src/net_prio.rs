@@ -0,0 +1,34 @@
+//! The v1 net_prio controller.
+
+use std::collections::HashMap;
+
+use {Controller, Result};
+
+/// Typed access to the v1 net_prio controller.
+pub struct NetPrioController {
+    inner: Controller,
+}
+
+impl NetPrioController {
+    /// Wrap a controller handle for the net_prio controller.
+    pub fn new(inner: Controller) -> NetPrioController {
+        NetPrioController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// The priority of traffic from this group on each network interface.
+    pub fn priorities(&self) -> Result<HashMap<String, u32>> {
+        let map = try!(self.inner.get_flat_keyed(b"net_prio.ifpriomap"));
+        Ok(map.into_iter().map(|(iface, prio)| (iface, prio as u32)).collect())
+    }
+
+    /// Set the priority of traffic from this group on one interface. Other interfaces keep
+    /// theirs.
+    pub fn set_priority(&self, iface: &str, prio: u32) -> Result<()> {
+        self.inner.set(b"net_prio.ifpriomap", format!("{} {}", iface, prio).as_slice())
+    }
+}
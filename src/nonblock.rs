@@ -0,0 +1,81 @@
+//! Watchers for event loops.
+//!
+//! Each watcher has a descriptor an event loop can wait on; once it's ready, `ready` takes the
+//! pending event without blocking, given the events the loop saw. This is what a reactor needs
+//! to drive the watchers alongside everything else, instead of parking a thread on each.
+
+use libc::{c_int, c_short};
+
+use {EventNotifier, FileWatcher, MemoryEvents, MemoryEventsWatcher, Result, Trigger};
+use sys;
+
+/// A watcher that can be driven by readiness notifications.
+pub trait AsyncWatch {
+    type Item;
+
+    /// The descriptor to wait on.
+    fn fd(&self) -> c_int;
+
+    /// The poll(2) events to wait for on it: POLLIN, or POLLPRI for PSI triggers.
+    fn interest(&self) -> c_short;
+
+    /// The pending event, if any; never blocks. A watcher whose cgroup is gone fails with
+    /// `KeyNotFound`.
+    fn try_next(&mut self) -> Result<Option<Self::Item>>;
+
+    /// The pending event, given the poll(2) events the reactor reported on `fd`; never blocks.
+    /// A PSI trigger's event is used up by the reactor's own poll, so only these say whether
+    /// it fired and `try_next` would miss it.
+    fn ready(&mut self, revents: c_short) -> Result<Option<Self::Item>> {
+        let _ = revents;
+        self.try_next()
+    }
+}
+
+impl AsyncWatch for FileWatcher {
+    type Item = ();
+
+    fn fd(&self) -> c_int { FileWatcher::fd(self) }
+    fn interest(&self) -> c_short { sys::POLLIN }
+
+    fn try_next(&mut self) -> Result<Option<()>> {
+        self.try_wait().map(|changed| if changed { Some(()) } else { None })
+    }
+}
+
+impl AsyncWatch for MemoryEventsWatcher {
+    type Item = MemoryEvents;
+
+    fn fd(&self) -> c_int { MemoryEventsWatcher::fd(self) }
+    fn interest(&self) -> c_short { sys::POLLIN }
+
+    fn try_next(&mut self) -> Result<Option<MemoryEvents>> {
+        self.try_wait()
+    }
+}
+
+impl AsyncWatch for EventNotifier {
+    type Item = u64;
+
+    fn fd(&self) -> c_int { EventNotifier::fd(self) }
+    fn interest(&self) -> c_short { sys::POLLIN }
+
+    fn try_next(&mut self) -> Result<Option<u64>> {
+        self.try_wait()
+    }
+}
+
+impl AsyncWatch for Trigger {
+    type Item = ();
+
+    fn fd(&self) -> c_int { Trigger::fd(self) }
+    fn interest(&self) -> c_short { sys::POLLPRI }
+
+    fn try_next(&mut self) -> Result<Option<()>> {
+        self.try_wait().map(|fired| if fired { Some(()) } else { None })
+    }
+
+    fn ready(&mut self, revents: c_short) -> Result<Option<()>> {
+        self.fired(revents as u32).map(|fired| if fired { Some(()) } else { None })
+    }
+}
@@ -0,0 +1,339 @@
+//! Parsers for the common control file formats.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Files with one `key value` pair per line and a known set of keys, like `cpu.stat`.
+pub trait FlatKeyed {
+    /// Build the typed value from a parsed file. Keys the type doesn't know about are ignored,
+    /// and keys the kernel doesn't provide are left at zero.
+    fn from_map(map: &HashMap<String, u64>) -> Self;
+}
+
+/// Define a struct with one `u64` field per key of a flat-keyed file, implementing `FlatKeyed`.
+macro_rules! flat_keyed {
+    ($(#[$attr:meta])* pub struct $name:ident {
+        $($(#[$fattr:meta])* $field:ident),+
+    }) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, PartialEq, Eq, Show, Default)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        pub struct $name {
+            $($(#[$fattr])* pub $field: u64),+
+        }
+
+        impl $name {
+            /// The change in every counter since an earlier reading. Counters that went
+            /// backwards, as when the cgroup was recreated, count from zero.
+            pub fn since(&self, earlier: &$name) -> $name {
+                $name {
+                    $($field: if self.$field >= earlier.$field {
+                        self.$field - earlier.$field
+                    } else {
+                        self.$field
+                    }),+
+                }
+            }
+        }
+
+        impl $crate::parse::FlatKeyed for $name {
+            fn from_map(map: &::std::collections::HashMap<String, u64>) -> $name {
+                $name {
+                    $($field: map.get(stringify!($field)).map(|&v| v).unwrap_or(0)),+
+                }
+            }
+        }
+    }
+}
+
+/// Parse a flat-keyed file, one `key value` pair per line. None if any line is malformed.
+pub fn flat_keyed(contents: &str) -> Option<HashMap<String, u64>> {
+    let mut map = HashMap::new();
+    for line in contents.lines() {
+        let mut words = line.words();
+        let key = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let value = match words.next().and_then(|v| v.parse()) {
+            Some(v) => v,
+            None => return None,
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        map.insert(key.to_string(), value);
+    }
+    Some(map)
+}
+
+/// A block device, as the kernel names it in per-device files.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Device {
+    pub major: u64,
+    pub minor: u64,
+}
+
+impl Device {
+    /// Parse the `major:minor` notation.
+    pub fn parse(s: &str) -> Option<Device> {
+        let mut parts = s.split(':');
+        let major = match parts.next().and_then(|m| m.parse()) {
+            Some(m) => m,
+            None => return None,
+        };
+        let minor = match parts.next().and_then(|m| m.parse()) {
+            Some(m) => m,
+            None => return None,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Device { major: major, minor: minor })
+    }
+}
+
+impl fmt::String for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.major, self.minor)
+    }
+}
+
+/// Parse a nested-keyed file into its raw per-device fields. None if any line is malformed.
+///
+/// Two layouts are understood, and may be mixed:
+///
+/// - v2 style, one line per device: `8:0 rbytes=1024 wbytes=0`
+/// - v1 style, one line per device and field: `8:0 Read 1024`
+/// - v1 style, one value per device: `8:0 12345`, as in `blkio.time` and `blkio.sectors`. The
+///   value is stored under the empty key, which no named field can clash with.
+///
+/// v1 files end with a `Total <n>` line that isn't tied to a device; it is skipped. Devices with
+/// nothing to report are simply absent, as they are in the file.
+pub fn nested_keyed_raw(contents: &str) -> Option<HashMap<Device, HashMap<String, String>>> {
+    let mut map = HashMap::new();
+    for line in contents.lines() {
+        let words: Vec<&str> = line.words().collect();
+        if words.is_empty() || (words.len() == 2 && words[0] == "Total") {
+            continue;
+        }
+        let dev = match Device::parse(words[0]) {
+            Some(d) => d,
+            None => return None,
+        };
+        if !map.contains_key(&dev) {
+            map.insert(dev, HashMap::new());
+        }
+        let fields = map.get_mut(&dev).unwrap();
+
+        if words.len() == 2 && !words[1].contains_char('=') {
+            fields.insert(String::new(), words[1].to_string());
+            continue;
+        }
+        if words.len() == 3 && !words[1].contains_char('=') {
+            fields.insert(words[1].to_string(), words[2].to_string());
+            continue;
+        }
+        for field in words[1..].iter() {
+            let mut kv = field.splitn(1, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if !k.is_empty() => {
+                    fields.insert(k.to_string(), v.to_string());
+                },
+                _ => return None,
+            }
+        }
+    }
+    Some(map)
+}
+
+/// Parse a nested-keyed file whose values are all numbers, like `io.stat`.
+pub fn nested_keyed(contents: &str) -> Option<HashMap<Device, HashMap<String, u64>>> {
+    let raw = match nested_keyed_raw(contents) {
+        Some(raw) => raw,
+        None => return None,
+    };
+    let mut map = HashMap::new();
+    for (dev, fields) in raw.into_iter() {
+        let mut parsed = HashMap::new();
+        for (k, v) in fields.into_iter() {
+            match v.as_slice().parse() {
+                Some(v) => { parsed.insert(k, v); },
+                None => return None,
+            }
+        }
+        map.insert(dev, parsed);
+    }
+    Some(map)
+}
+
+/// Parse a file with one `name key=value key=value` line per named resource, like
+/// `rdma.max`. None if any line is malformed.
+pub fn named_keyed_raw(contents: &str) -> Option<HashMap<String, HashMap<String, String>>> {
+    let mut map = HashMap::new();
+    for line in contents.lines() {
+        let mut words = line.words();
+        let name = match words.next() {
+            Some(n) => n,
+            None => continue,
+        };
+        let mut fields = HashMap::new();
+        for field in words {
+            let mut kv = field.splitn(1, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if !k.is_empty() => {
+                    fields.insert(k.to_string(), v.to_string());
+                },
+                _ => return None,
+            }
+        }
+        map.insert(name.to_string(), fields);
+    }
+    Some(map)
+}
+
+/// Parse `memory.numa_stat` into per-node counts for each category. None if any line is
+/// malformed.
+///
+/// Both layouts are understood: v1's `total=<n> N0=<n> N1=<n>`, with the category before the
+/// `=` of the first field (its total is dropped), and v2's `anon N0=<n> N1=<n>`. The counts are
+/// left in the file's units, pages in v1 and bytes in v2.
+pub fn numa_stat(contents: &str) -> Option<HashMap<String, BTreeMap<u32, u64>>> {
+    let mut map = HashMap::new();
+    for line in contents.lines() {
+        let mut words = line.words();
+        let first = match words.next() {
+            Some(w) => w,
+            None => continue,
+        };
+        let name = match first.find('=') {
+            Some(i) => &first[..i],
+            None => first,
+        };
+        let mut nodes = BTreeMap::new();
+        for field in words {
+            let mut kv = field.splitn(1, '=');
+            let node = kv.next().and_then(|k| {
+                if k.starts_with("N") { k[1..].parse() } else { None }
+            });
+            match (node, kv.next().and_then(|v| v.parse())) {
+                (Some(node), Some(v)) => { nodes.insert(node, v); },
+                _ => return None,
+            }
+        }
+        map.insert(name.to_string(), nodes);
+    }
+    Some(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{flat_keyed, nested_keyed, nested_keyed_raw, numa_stat, Device};
+
+    fn field<'a>(map: &'a HashMap<Device, HashMap<String, String>>, dev: &str, key: &str)
+                 -> Option<&'a str> {
+        map.get(&Device::parse(dev).unwrap()).and_then(|f| f.get(key)).map(|v| v.as_slice())
+    }
+
+    #[test]
+    fn flat() {
+        let map = flat_keyed("usage_usec 100\nuser_usec 60\n\nsystem_usec 40\n").unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get("usage_usec"), Some(&100));
+        assert_eq!(map.get("system_usec"), Some(&40));
+        assert_eq!(flat_keyed("").unwrap().len(), 0);
+        // v1 memory.stat style, with total_ keys alongside the local ones
+        let map = flat_keyed("cache 4096\ntotal_cache 8192\n").unwrap();
+        assert_eq!(map.get("total_cache"), Some(&8192));
+    }
+
+    #[test]
+    fn flat_malformed() {
+        assert!(flat_keyed("usage_usec\n").is_none());
+        assert!(flat_keyed("usage_usec x\n").is_none());
+        assert!(flat_keyed("usage_usec -1\n").is_none());
+        assert!(flat_keyed("usage_usec 1 2\n").is_none());
+        assert!(flat_keyed("ok 1\nbad\n").is_none());
+    }
+
+    #[test]
+    fn devices() {
+        assert_eq!(Device::parse("8:16"), Some(Device { major: 8, minor: 16 }));
+        assert_eq!(Device::parse("8"), None);
+        assert_eq!(Device::parse("8:16:1"), None);
+        assert_eq!(Device::parse("sda"), None);
+        assert_eq!(format!("{}", Device { major: 253, minor: 0 }).as_slice(), "253:0");
+    }
+
+    #[test]
+    fn nested_v2() {
+        let map = nested_keyed_raw("8:0 rbytes=1024 wbytes=0 rios=3\n253:0 rbytes=5\n").unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(field(&map, "8:0", "rbytes"), Some("1024"));
+        assert_eq!(field(&map, "8:0", "rios"), Some("3"));
+        assert_eq!(field(&map, "253:0", "wbytes"), None);
+        // io.max style values needn't be numbers
+        let map = nested_keyed_raw("8:0 rbps=max wiops=100\n").unwrap();
+        assert_eq!(field(&map, "8:0", "rbps"), Some("max"));
+    }
+
+    #[test]
+    fn nested_v1() {
+        let map = nested_keyed_raw("\
+8:0 Read 1024
+8:0 Write 512
+8:16 Read 3
+Total 1539
+").unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(field(&map, "8:0", "Read"), Some("1024"));
+        assert_eq!(field(&map, "8:0", "Write"), Some("512"));
+        assert_eq!(field(&map, "8:16", "Read"), Some("3"));
+    }
+
+    #[test]
+    fn nested_single_value() {
+        let map = nested_keyed_raw("8:0 12345\n8:16 7\nTotal 12352\n").unwrap();
+        assert_eq!(field(&map, "8:0", ""), Some("12345"));
+        assert_eq!(field(&map, "8:16", ""), Some("7"));
+    }
+
+    #[test]
+    fn nested_mixed() {
+        let map = nested_keyed("8:0 rbytes=1 wbytes=2\n8:0 Read 3\n8:16 9\nTotal 12\n").unwrap();
+        let sda = map.get(&Device { major: 8, minor: 0 }).unwrap();
+        assert_eq!(sda.get("rbytes"), Some(&1));
+        assert_eq!(sda.get("wbytes"), Some(&2));
+        assert_eq!(sda.get("Read"), Some(&3));
+        assert_eq!(map.get(&Device { major: 8, minor: 16 }).unwrap().get(""), Some(&9));
+    }
+
+    #[test]
+    fn nested_malformed() {
+        assert!(nested_keyed_raw("sda rbytes=1\n").is_none());
+        assert!(nested_keyed_raw("8:0 =5\n").is_none());
+        assert!(nested_keyed_raw("8:0 rbytes=1 wbytes\n").is_none());
+        assert!(nested_keyed_raw("8:0 Read 1 2\n").is_none());
+        // a Total line has exactly one value
+        assert!(nested_keyed_raw("Total\n").is_none());
+        assert!(nested_keyed_raw("Total 1 2\n").is_none());
+        // fine as text, not as numbers
+        assert!(nested_keyed_raw("8:0 Read x\n").is_some());
+        assert!(nested_keyed("8:0 Read x\n").is_none());
+        assert!(nested_keyed("8:0 rbps=max\n").is_none());
+    }
+
+    #[test]
+    fn numa() {
+        let v1 = numa_stat("total=100 N0=60 N1=40\nfile=10 N0=10 N1=0\n").unwrap();
+        assert_eq!(v1.get("total").unwrap().get(&1), Some(&40));
+        assert_eq!(v1.get("file").unwrap().get(&0), Some(&10));
+        let v2 = numa_stat("anon N0=4096 N1=0\n").unwrap();
+        assert_eq!(v2.get("anon").unwrap().len(), 2);
+        assert!(numa_stat("anon 4096 N0=4096\n").is_none());
+        assert!(numa_stat("anon X0=1\n").is_none());
+    }
+}
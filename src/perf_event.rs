@@ -0,0 +1,15 @@
+//! The perf_event controller.
+
+use libc;
+
+use {Controller, Result};
+use sys::{self, Fd};
+
+impl Controller {
+    /// Open this cgroup's directory for `perf_event_open(2)` with `PERF_FLAG_PID_CGROUP`, which
+    /// takes the descriptor in place of a pid. The controller should be the perf_event one on v1;
+    /// on v2 any handle for the cgroup works.
+    pub fn perf_event_fd(&self) -> Result<Fd> {
+        Fd::open(self.path(), libc::O_RDONLY | sys::O_DIRECTORY)
+    }
+}
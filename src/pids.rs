@@ -0,0 +1,63 @@
+//! The pids controller.
+
+use {Controller, Error, Limit, Result};
+
+flat_keyed! {
+    /// Counters from `pids.events`.
+    pub struct PidsEvents {
+        /// Forks rejected because the group was at its limit
+        max
+    }
+}
+
+/// Typed access to the pids controller.
+pub struct PidsController {
+    inner: Controller,
+}
+
+impl PidsController {
+    /// Wrap a controller handle for the pids controller.
+    pub fn new(inner: Controller) -> PidsController {
+        PidsController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    /// The number of processes and threads in the group.
+    pub fn current(&self) -> Result<u64> {
+        self.inner.get_u64(b"pids.current")
+    }
+
+    /// The most processes and threads the group may have.
+    pub fn max(&self) -> Result<Limit> {
+        self.inner.get_limit(b"pids.max")
+    }
+
+    /// The most processes and threads the group can actually have: the smallest `pids.max` of
+    /// the group and its ancestors.
+    pub fn effective_max(&self) -> Result<Limit> {
+        let mut max = try!(self.max());
+        for ancestor in try!(self.inner.ancestors()).into_iter() {
+            match PidsController::new(ancestor).max() {
+                Ok(m) => if m < max { max = m },
+                // the root has no pids.max
+                Err(Error::KeyNotFound(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(max)
+    }
+
+    /// Set the most processes and threads the group may have.
+    pub fn set_max(&self, max: Limit) -> Result<()> {
+        self.inner.set(b"pids.max", format!("{}", max).as_slice())
+    }
+
+    /// The fork-rejection counters from `pids.events`.
+    pub fn events(&self) -> Result<PidsEvents> {
+        self.inner.get_flat_keyed_as(b"pids.events")
+    }
+}
@@ -0,0 +1,151 @@
+//! Rendering cgroup metrics in the Prometheus text exposition format.
+//!
+//! Every sample is labelled with the cgroup's path in its hierarchy and the controller it came
+//! from. Metrics a cgroup doesn't have, because the controller isn't available or the kernel
+//! doesn't know the file, are left out.
+
+use std::collections::BTreeMap;
+
+use {CGroup, Limit};
+use psi::Resource;
+
+/// Samples of one metric family, with its help text and type.
+struct Family {
+    help: &'static str,
+    kind: &'static str,
+    samples: Vec<String>,
+}
+
+/// Metric families by name, so each is rendered once with all of its samples together.
+struct Registry {
+    families: BTreeMap<&'static str, Family>,
+}
+
+impl Registry {
+    fn add(&mut self, name: &'static str, kind: &'static str, help: &'static str,
+           labels: &[(&str, &str)], value: f64) {
+        let labels: Vec<String> = labels.iter()
+            .map(|&(k, v)| format!("{}=\"{}\"", k, escape(v)))
+            .collect();
+        let value = if value.is_infinite() { "+Inf".to_string() } else { value.to_string() };
+        let sample = format!("{}{{{}}} {}", name, labels.connect(","), value);
+        self.families.entry(name).get()
+            .unwrap_or_else(|e| e.insert(Family { help: help, kind: kind, samples: Vec::new() }))
+            .samples.push(sample);
+    }
+}
+
+/// Escape a label value.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl CGroup {
+    /// The path of this process's cgroup in the hierarchy a controller is in.
+    fn cgroup_path(&self, controller: &[u8]) -> String {
+        self.hierarchy_key(controller).and_then(|h| self.controllers.get(h))
+            .or(self.unified.as_ref())
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "/".to_string())
+    }
+
+    fn collect(&self, reg: &mut Registry) {
+        if let Ok(memory) = self.memory() {
+            let path = self.cgroup_path(b"memory");
+            let labels = [("cgroup", path.as_slice()), ("controller", "memory")];
+            if let Ok(usage) = memory.usage() {
+                reg.add("cgroup_memory_usage_bytes", "gauge", "Memory in use by the cgroup.",
+                        &labels, usage as f64);
+            }
+            if let Ok(limit) = memory.limit() {
+                let limit = limit.value().map(|l| l as f64).unwrap_or(::std::f64::INFINITY);
+                reg.add("cgroup_memory_limit_bytes", "gauge", "Memory limit of the cgroup.",
+                        &labels, limit);
+            }
+        }
+
+        if let Ok(cpu) = self.cpu() {
+            let path = self.cgroup_path(b"cpu");
+            let labels = [("cgroup", path.as_slice()), ("controller", "cpu")];
+            if let Ok(usage) = cpu.usage() {
+                reg.add("cgroup_cpu_usage_seconds_total", "counter",
+                        "CPU time used by the cgroup.", &labels, usage.usage_ns as f64 / 1e9);
+            }
+            if let Ok(stat) = cpu.stat() {
+                let throttled_ns = stat.throttled_time + stat.throttled_usec * 1000;
+                reg.add("cgroup_cpu_periods_total", "counter",
+                        "CPU enforcement periods elapsed.", &labels, stat.nr_periods as f64);
+                reg.add("cgroup_cpu_throttled_periods_total", "counter",
+                        "CPU enforcement periods in which the cgroup was throttled.", &labels,
+                        stat.nr_throttled as f64);
+                reg.add("cgroup_cpu_throttled_seconds_total", "counter",
+                        "Time the cgroup was throttled for.", &labels, throttled_ns as f64 / 1e9);
+            }
+        }
+
+        if let Ok(pids) = self.pids() {
+            let path = self.cgroup_path(b"pids");
+            let labels = [("cgroup", path.as_slice()), ("controller", "pids")];
+            if let Ok(current) = pids.current() {
+                reg.add("cgroup_pids_current", "gauge", "Processes and threads in the cgroup.",
+                        &labels, current as f64);
+            }
+            if let Ok(max) = pids.max() {
+                let max = match max {
+                    Limit::Max => ::std::f64::INFINITY,
+                    Limit::Value(v) => v as f64,
+                };
+                reg.add("cgroup_pids_max", "gauge", "Process and thread limit of the cgroup.",
+                        &labels, max);
+            }
+        }
+
+        if let Ok(unified) = self.unified() {
+            let path = self.cgroup_path(b"");
+            for &resource in [Resource::Cpu, Resource::Memory, Resource::Io].iter() {
+                let pressure = match unified.pressure(resource) {
+                    Ok(p) => p,
+                    Err(_) => continue,
+                };
+                let labels = [("cgroup", path.as_slice()), ("controller", resource.name()),
+                              ("resource", resource.name())];
+                reg.add("cgroup_pressure_some_seconds_total", "counter",
+                        "Time some tasks of the cgroup were stalled on a resource.", &labels,
+                        pressure.some.total as f64 / 1e6);
+                if let Some(full) = pressure.full {
+                    reg.add("cgroup_pressure_full_seconds_total", "counter",
+                            "Time all tasks of the cgroup were stalled on a resource.", &labels,
+                            full.total as f64 / 1e6);
+                }
+            }
+        }
+    }
+}
+
+/// Render the metrics of a set of cgroups.
+pub fn render(cgroups: &[CGroup]) -> String {
+    let mut reg = Registry { families: BTreeMap::new() };
+    for cg in cgroups.iter() {
+        cg.collect(&mut reg);
+    }
+
+    let mut out = String::new();
+    for (name, family) in reg.families.iter() {
+        out.push_str(format!("# HELP {} {}\n# TYPE {} {}\n", name, family.help, name,
+                             family.kind).as_slice());
+        for sample in family.samples.iter() {
+            out.push_str(sample.as_slice());
+            out.push('\n');
+        }
+    }
+    out
+}
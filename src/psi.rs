@@ -0,0 +1,229 @@
+//! Pressure stall information, from the v2 `*.pressure` files.
+
+use std::collections::HashMap;
+
+use libc;
+
+use {Controller, Error, Result};
+use parse;
+use sys::{self, Fd};
+
+/// A resource that stalls are tracked for.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Resource {
+    Cpu,
+    Memory,
+    Io,
+    Irq,
+}
+
+impl Resource {
+    /// The name of the pressure file for this resource, like `cpu.pressure`.
+    pub fn key(&self) -> &'static [u8] {
+        match *self {
+            Resource::Cpu => b"cpu.pressure",
+            Resource::Memory => b"memory.pressure",
+            Resource::Io => b"io.pressure",
+            Resource::Irq => b"irq.pressure",
+        }
+    }
+
+    /// The resource's name, like `cpu`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Resource::Cpu => "cpu",
+            Resource::Memory => "memory",
+            Resource::Io => "io",
+            Resource::Irq => "irq",
+        }
+    }
+}
+
+/// Stall figures for one kind of stall.
+#[derive(Clone, Copy, PartialEq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StallStats {
+    /// Percentage of time stalled over the last 10 seconds
+    pub avg10: f64,
+    /// Percentage of time stalled over the last 60 seconds
+    pub avg60: f64,
+    /// Percentage of time stalled over the last 300 seconds
+    pub avg300: f64,
+    /// Total time stalled, in microseconds
+    pub total: u64,
+}
+
+impl StallStats {
+    fn from_fields(fields: &HashMap<String, String>) -> Option<StallStats> {
+        let avg = |&: name: &str| fields.get(name).and_then(|v| v.as_slice().parse::<f64>());
+        match (avg("avg10"), avg("avg60"), avg("avg300"),
+               fields.get("total").and_then(|v| v.as_slice().parse::<u64>())) {
+            (Some(a10), Some(a60), Some(a300), Some(total)) => Some(StallStats {
+                avg10: a10,
+                avg60: a60,
+                avg300: a300,
+                total: total,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The contents of a pressure file.
+#[derive(Clone, Copy, PartialEq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pressure {
+    /// Time at least one task was stalled on the resource
+    pub some: StallStats,
+    /// Time every non-idle task was stalled at once. Older kernels leave it out of
+    /// `cpu.pressure`.
+    pub full: Option<StallStats>,
+}
+
+impl Pressure {
+    /// Parse the `some avg10=... avg60=... avg300=... total=...` format.
+    pub fn parse(s: &str) -> Option<Pressure> {
+        let lines = match parse::named_keyed_raw(s) {
+            Some(l) => l,
+            None => return None,
+        };
+        let some = match lines.get("some").and_then(StallStats::from_fields) {
+            Some(s) => s,
+            None => return None,
+        };
+        let full = match lines.get("full") {
+            Some(f) => match StallStats::from_fields(f) {
+                Some(f) => Some(f),
+                None => return None,
+            },
+            None => None,
+        };
+        Some(Pressure { some: some, full: full })
+    }
+}
+
+/// Which kind of stall a trigger watches.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StallKind {
+    /// At least one task stalled
+    Some,
+    /// Every non-idle task stalled at once
+    Full,
+}
+
+/// A registered PSI trigger, firing whenever the stall time within a window goes over the
+/// threshold. It stays registered until dropped.
+///
+/// As an iterator it blocks until each firing, and ends once the cgroup is removed.
+pub struct Trigger {
+    fd: Fd,
+}
+
+impl Trigger {
+    /// Register a trigger on a pressure file, stalled for more than `threshold_us` microseconds
+    /// in any `window_us` window. The kernel takes windows of 500ms to 10s.
+    pub fn register(path: &Path, kind: StallKind, threshold_us: u64, window_us: u64)
+                    -> Result<Trigger> {
+        if threshold_us == 0 || threshold_us > window_us ||
+           window_us < 500000 || window_us > 10000000 {
+            return Err(Error::InvalidValue(path.clone(), format!(
+                "PSI trigger threshold {}us must be within a window of 500ms to 10s, not {}us",
+                threshold_us, window_us)));
+        }
+        let fd = try!(Fd::open(path, libc::O_RDWR | sys::O_NONBLOCK));
+        let kind = match kind {
+            StallKind::Some => "some",
+            StallKind::Full => "full",
+        };
+        // The kernel replaces the last byte written with a NUL, so send one ourselves.
+        let spec = format!("{} {} {}\0", kind, threshold_us, window_us);
+        try!(fd.write(spec.as_bytes()));
+        Ok(Trigger { fd: fd })
+    }
+
+    /// Block until the trigger fires. False if it never will again, because the cgroup is gone.
+    pub fn wait(&self) -> Result<bool> {
+        self.wait_timeout(-1)
+    }
+
+    /// Block until the trigger fires, for at most `timeout_ms` milliseconds. False on timeout
+    /// or once the cgroup is gone.
+    pub fn wait_timeout(&self, timeout_ms: i32) -> Result<bool> {
+        let revents = try!(self.fd.poll(sys::POLLPRI, timeout_ms));
+        Ok(revents & sys::POLLPRI != 0 && revents & sys::POLLERR == 0)
+    }
+
+    /// Whether the trigger fired, without blocking. Fails with `KeyNotFound` once the cgroup is
+    /// gone.
+    pub fn try_wait(&self) -> Result<bool> {
+        let revents = try!(self.fd.poll(sys::POLLPRI, 0));
+        self.fired(revents as u32)
+    }
+
+    /// Whether the trigger fired, going by the events a poll(2) or epoll wait of the caller's
+    /// own reported on its descriptor (the bits are the same for both). Fails with
+    /// `KeyNotFound` once the cgroup is gone.
+    ///
+    /// The kernel reports each event to one poll only, so once an event loop has been told
+    /// the descriptor is ready, `try_wait` would find nothing: this is what to call instead.
+    pub fn fired(&self, revents: u32) -> Result<bool> {
+        if revents & sys::EPOLLERR != 0 {
+            return Err(Error::KeyNotFound(self.fd.path().clone()));
+        }
+        Ok(revents & sys::EPOLLPRI != 0)
+    }
+
+    /// The trigger's descriptor, for use with poll(2) (waiting for POLLPRI) and friends.
+    pub fn fd(&self) -> libc::c_int {
+        self.fd.raw()
+    }
+}
+
+impl Iterator for Trigger {
+    type Item = Result<()>;
+
+    fn next(&mut self) -> Option<Result<()>> {
+        match self.wait() {
+            Ok(true) => Some(Ok(())),
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Register a trigger on pressure for the whole system, in `/proc/pressure`.
+pub fn system_trigger(resource: Resource, kind: StallKind, threshold_us: u64, window_us: u64)
+                      -> Result<Trigger> {
+    Trigger::register(&Path::new("/proc/pressure").join(resource.name()),
+                      kind, threshold_us, window_us)
+}
+
+/// Pressure on a resource for the whole system, from `/proc/pressure`.
+pub fn system_pressure(resource: Resource) -> Result<Pressure> {
+    let p = Path::new("/proc/pressure").join(resource.name());
+    let s = try!(::read_file(&p));
+    match Pressure::parse(s.as_slice()) {
+        Some(pressure) => Ok(pressure),
+        None => Err(Error::InvalidValue(p, format!("{:?}", s.as_slice().trim()))),
+    }
+}
+
+impl Controller {
+    /// Pressure on a resource for this cgroup. Only v2 cgroups have pressure files.
+    pub fn pressure(&self, resource: Resource) -> Result<Pressure> {
+        let key = resource.key();
+        let s = try!(self.get(key));
+        match Pressure::parse(s.as_slice()) {
+            Some(p) => Ok(p),
+            None => Err(self.invalid_value(key, s.as_slice())),
+        }
+    }
+
+    /// Register a PSI trigger on this cgroup's pressure for a resource.
+    pub fn pressure_trigger(&self, resource: Resource, kind: StallKind, threshold_us: u64,
+                            window_us: u64) -> Result<Trigger> {
+        Trigger::register(&self.path().join(resource.key()), kind, threshold_us, window_us)
+    }
+}
@@ -0,0 +1,81 @@
+//! The rdma controller.
+
+use std::collections::HashMap;
+
+use {Controller, Limit, Result};
+use parse;
+
+/// RDMA resources of one HCA device.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RdmaResources<T> {
+    /// HCA handles
+    pub hca_handle: T,
+    /// HCA objects
+    pub hca_object: T,
+}
+
+/// Typed access to the rdma controller, keyed by HCA device name.
+pub struct RdmaController {
+    inner: Controller,
+}
+
+impl RdmaController {
+    /// Wrap a controller handle for the rdma controller.
+    pub fn new(inner: Controller) -> RdmaController {
+        RdmaController { inner: inner }
+    }
+
+    /// The underlying controller, for files this type doesn't cover.
+    pub fn controller(&self) -> &Controller {
+        &self.inner
+    }
+
+    fn get_resources(&self, key: &[u8]) -> Result<HashMap<String, RdmaResources<Limit>>> {
+        let s = try!(self.inner.get(key));
+        let raw = match parse::named_keyed_raw(s.as_slice()) {
+            Some(raw) => raw,
+            None => return Err(self.inner.invalid_value(key, s.as_slice())),
+        };
+
+        let mut map = HashMap::new();
+        for (dev, fields) in raw.into_iter() {
+            let handle = fields.get("hca_handle").and_then(|v| Limit::parse(v.as_slice()));
+            let object = fields.get("hca_object").and_then(|v| Limit::parse(v.as_slice()));
+            match (handle, object) {
+                (Some(h), Some(o)) => {
+                    map.insert(dev, RdmaResources { hca_handle: h, hca_object: o });
+                },
+                _ => return Err(self.inner.invalid_value(key, s.as_slice())),
+            }
+        }
+        Ok(map)
+    }
+
+    /// The resources in use on each device, from `rdma.current`.
+    pub fn current(&self) -> Result<HashMap<String, RdmaResources<u64>>> {
+        let map = try!(self.get_resources(b"rdma.current"));
+        let mut current = HashMap::new();
+        for (dev, r) in map.into_iter() {
+            match (r.hca_handle, r.hca_object) {
+                (Limit::Value(h), Limit::Value(o)) => {
+                    current.insert(dev, RdmaResources { hca_handle: h, hca_object: o });
+                },
+                _ => return Err(self.inner.invalid_value(b"rdma.current", "max")),
+            }
+        }
+        Ok(current)
+    }
+
+    /// The limits on each device, from `rdma.max`.
+    pub fn max(&self) -> Result<HashMap<String, RdmaResources<Limit>>> {
+        self.get_resources(b"rdma.max")
+    }
+
+    /// Set the limits on one device.
+    pub fn set_max(&self, device: &str, limits: RdmaResources<Limit>) -> Result<()> {
+        let value = format!("{} hca_handle={} hca_object={}",
+                            device, limits.hca_handle, limits.hca_object);
+        self.inner.set(b"rdma.max", value.as_slice())
+    }
+}
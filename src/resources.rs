@@ -0,0 +1,179 @@
+//! The common resource settings of a cgroup, set together.
+
+use {CGroup, Limit, Result, Version};
+use compat::Setting;
+
+/// Settings for the knobs most workloads need, in the spirit of the OCI runtime spec's
+/// resources block. Settings left as None aren't touched.
+///
+/// Weights are on the v2 scale; see `Resources::apply` for v1.
+#[derive(Clone, PartialEq, Eq, Show, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Resources {
+    /// The hard memory limit in bytes
+    pub memory_max: Option<Limit>,
+    /// The limit on swap in bytes, on top of the memory limit
+    pub memory_swap: Option<Limit>,
+    /// The relative weight of CPU time, from 1 to 10000
+    pub cpu_weight: Option<u64>,
+    /// The CPU time the group may use per period, in microseconds
+    pub cpu_quota: Option<Limit>,
+    /// The length of an enforcement period, in microseconds
+    pub cpu_period: Option<u64>,
+    /// The CPUs the group may run on
+    pub cpus: Option<Vec<usize>>,
+    /// The memory nodes the group may allocate from
+    pub mems: Option<Vec<usize>>,
+    /// The most processes and threads the group may have
+    pub pids_max: Option<Limit>,
+    /// The relative weight of IO, from 1 to 10000
+    pub io_weight: Option<u64>,
+}
+
+impl Resources {
+    /// Write every setting to the cgroups of `cg`, stopping at the first failure. A setting
+    /// for a controller `cg` doesn't have fails with `ControllerNotMounted`.
+    ///
+    /// Settings go in an order the kernel accepts: the cpuset first, as v1 won't take
+    /// processes into a cgroup without one; the period before the quota; and on v1 the memory
+    /// limit and the memory-plus-swap limit (the memory limit plus `memory_swap`) in whichever
+    /// order keeps the second at least the first. Without `memory_swap`, v1 only gets the
+    /// memory limit, which it refuses over the memory-plus-swap limit in force. v1 gets the CPU
+    /// weight as `cpu.shares`, converted by `compat::weight_to_shares`.
+    pub fn apply(&self, cg: &CGroup) -> Result<()> {
+        if self.cpus.is_some() || self.mems.is_some() {
+            let cpuset = try!(cg.cpuset());
+            if let Some(ref cpus) = self.cpus {
+                try!(cpuset.set_cpus(cpus.as_slice()));
+            }
+            if let Some(ref mems) = self.mems {
+                try!(cpuset.set_mems(mems.as_slice()));
+            }
+        }
+
+        if self.cpu_weight.is_some() || self.cpu_quota.is_some() || self.cpu_period.is_some() {
+            let cpu = try!(cg.cpu());
+            if let Some(weight) = self.cpu_weight {
+                try!(Setting::CpuWeight(weight).write(cpu.controller()));
+            }
+            if let Some(period) = self.cpu_period {
+                try!(cpu.set_period(period));
+            }
+            if let Some(quota) = self.cpu_quota {
+                try!(cpu.set_quota(quota));
+            }
+        }
+
+        if self.memory_max.is_some() || self.memory_swap.is_some() {
+            try!(self.apply_memory(cg));
+        }
+
+        if let Some(max) = self.pids_max {
+            try!(try!(cg.pids()).set_max(max));
+        }
+
+        if let Some(weight) = self.io_weight {
+            try!(match cg.io() {
+                Ok(io) => io.controller().set_io_weight(Some(weight), &[]),
+                Err(_) => try!(cg.blkio()).controller().set_io_weight(Some(weight), &[]),
+            });
+        }
+        Ok(())
+    }
+
+    fn apply_memory(&self, cg: &CGroup) -> Result<()> {
+        let memory = try!(cg.memory());
+        match memory.controller().version() {
+            Version::V1 => match self.memory_swap {
+                None => match self.memory_max {
+                    Some(max) => memory.set_limit(max),
+                    None => Ok(()),
+                },
+                Some(swap) => {
+                    let limit = match self.memory_max {
+                        Some(max) => max,
+                        None => try!(memory.limit()),
+                    };
+                    let memsw = match (limit, swap) {
+                        (Limit::Value(l), Limit::Value(s)) => Limit::Value(l + s),
+                        _ => Limit::Max,
+                    };
+                    memory.set_limit_with_swap(limit, memsw)
+                },
+            },
+            Version::V2 => {
+                if let Some(max) = self.memory_max {
+                    try!(memory.set_limit(max));
+                }
+                if let Some(swap) = self.memory_swap {
+                    try!(memory.set_swap_max(swap));
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Builds `Resources` one setting at a time.
+#[derive(Clone, Default)]
+pub struct LimitsBuilder {
+    resources: Resources,
+}
+
+impl LimitsBuilder {
+    /// A builder with nothing set.
+    pub fn new() -> LimitsBuilder {
+        Default::default()
+    }
+
+    pub fn memory_max(mut self, max: Limit) -> LimitsBuilder {
+        self.resources.memory_max = Some(max);
+        self
+    }
+
+    pub fn memory_swap(mut self, swap: Limit) -> LimitsBuilder {
+        self.resources.memory_swap = Some(swap);
+        self
+    }
+
+    pub fn cpu_weight(mut self, weight: u64) -> LimitsBuilder {
+        self.resources.cpu_weight = Some(weight);
+        self
+    }
+
+    pub fn cpu_quota(mut self, quota: Limit, period: u64) -> LimitsBuilder {
+        self.resources.cpu_quota = Some(quota);
+        self.resources.cpu_period = Some(period);
+        self
+    }
+
+    pub fn cpus(mut self, cpus: &[usize]) -> LimitsBuilder {
+        self.resources.cpus = Some(cpus.to_vec());
+        self
+    }
+
+    pub fn mems(mut self, mems: &[usize]) -> LimitsBuilder {
+        self.resources.mems = Some(mems.to_vec());
+        self
+    }
+
+    pub fn pids_max(mut self, max: Limit) -> LimitsBuilder {
+        self.resources.pids_max = Some(max);
+        self
+    }
+
+    pub fn io_weight(mut self, weight: u64) -> LimitsBuilder {
+        self.resources.io_weight = Some(weight);
+        self
+    }
+
+    /// The settings built up so far.
+    pub fn build(self) -> Resources {
+        self.resources
+    }
+
+    /// Apply the settings to the cgroups of `cg`, as `Resources::apply` does.
+    pub fn apply(&self, cg: &CGroup) -> Result<()> {
+        self.resources.apply(cg)
+    }
+}
@@ -0,0 +1,152 @@
+//! Reading control files periodically, keeping a bounded history.
+
+use std::collections::{BTreeMap, RingBuf};
+use std::io::timer;
+use std::time::Duration;
+use time;
+
+use {Controller, KeyFile, Result, Timestamp};
+
+/// The values of a sampler's keys at one point in time.
+#[derive(Clone, PartialEq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Sample {
+    pub time: Timestamp,
+    /// Monotonic clock in nanoseconds, for rates between samples
+    pub monotonic_ns: u64,
+    /// Contents of each key, by the cgroup directory it's in and then the key. Keys that
+    /// couldn't be read this time are missing.
+    pub values: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Sample {
+    /// The value of a key, from whichever cgroup has it.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.values().filter_map(|v| v.get(key)).next().map(|v| v.as_slice())
+    }
+}
+
+struct SampledKey {
+    controller: Controller,
+    key: Vec<u8>,
+    /// The open file, with `keep_open`
+    file: Option<KeyFile>,
+}
+
+impl SampledKey {
+    fn read(&mut self, keep_open: bool) -> Result<String> {
+        if !keep_open {
+            return self.controller.get(self.key.as_slice());
+        }
+        if self.file.is_none() {
+            self.file = Some(try!(self.controller.open_key(self.key.as_slice())));
+        }
+        let result = self.file.as_ref().unwrap().read();
+        if result.is_err() {
+            // most likely the cgroup is gone; try opening it again next time
+            self.file = None;
+        }
+        result
+    }
+}
+
+/// Reads a set of keys across controllers every `interval`, keeping the last `capacity`
+/// samples.
+pub struct Sampler {
+    keys: Vec<SampledKey>,
+    interval: Duration,
+    keep_open: bool,
+    capacity: usize,
+    history: RingBuf<Sample>,
+}
+
+impl Sampler {
+    /// A sampler with no keys yet.
+    pub fn new(interval: Duration, capacity: usize) -> Sampler {
+        Sampler {
+            keys: Vec::new(),
+            interval: interval,
+            keep_open: false,
+            capacity: capacity,
+            history: RingBuf::with_capacity(capacity),
+        }
+    }
+
+    /// Read `key` of `controller` in every sample from now on.
+    pub fn add(&mut self, controller: &Controller, key: &[u8]) -> Result<()> {
+        self.keys.push(SampledKey {
+            controller: try!(controller.reopen()),
+            key: key.to_vec(),
+            file: None,
+        });
+        Ok(())
+    }
+
+    /// Keep the files open between samples, rereading them in place rather than opening each
+    /// one for every sample. Worth it for many keys at short intervals; off by default.
+    pub fn set_keep_open(&mut self, keep_open: bool) {
+        self.keep_open = keep_open;
+        if !keep_open {
+            for k in self.keys.iter_mut() {
+                k.file = None;
+            }
+        }
+    }
+
+    /// Take a sample right away, dropping the oldest one if the history is full.
+    pub fn sample(&mut self) -> &Sample {
+        let time = Timestamp::now();
+        let monotonic_ns = time::precise_time_ns();
+        let mut values = BTreeMap::new();
+        let keep_open = self.keep_open;
+        for k in self.keys.iter_mut() {
+            if let Ok(v) = k.read(keep_open) {
+                let dir = k.controller.path().display().to_string();
+                let key = String::from_utf8_lossy(k.key.as_slice()).into_owned();
+                let v = v.as_slice().trim_right_matches('\n').to_string();
+                values.entry(dir).get().unwrap_or_else(|e| e.insert(BTreeMap::new()))
+                      .insert(key, v);
+            }
+        }
+
+        if self.capacity == 0 {
+            self.history.clear();
+        } else if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(Sample { time: time, monotonic_ns: monotonic_ns, values: values });
+        self.history.back().expect("just pushed a sample")
+    }
+
+    /// Block until an interval has passed since the last sample, then take the next one.
+    pub fn tick(&mut self) -> &Sample {
+        if let Some(last) = self.history.back().map(|s| s.monotonic_ns) {
+            let elapsed = Duration::nanoseconds((time::precise_time_ns() - last) as i64);
+            if elapsed < self.interval {
+                timer::sleep(self.interval - elapsed);
+            }
+        }
+        self.sample()
+    }
+
+    /// The most recent sample.
+    pub fn last(&self) -> Option<&Sample> {
+        self.history.back()
+    }
+
+    /// The most recent `n` samples (fewer if there aren't as many yet), oldest first.
+    pub fn latest(&self, n: usize) -> Vec<&Sample> {
+        let skip = if self.history.len() > n { self.history.len() - n } else { 0 };
+        self.history.iter().skip(skip).collect()
+    }
+
+    /// How many samples are kept.
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// The time between samples when using `tick`.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
@@ -0,0 +1,472 @@
+//! The control files the kernel is known to have, with what can be written to them.
+//!
+//! The tables cover the files documented for each hierarchy; a key missing from them is
+//! more likely a typo than a new file, but `validate` is only a check and nothing else here
+//! refuses keys it doesn't know. `*` in a name stands for a hugetlb page size, like `2MB`.
+
+use std::fmt;
+
+use {Controller, Error, Result, Version};
+use size;
+use self::Access::{ReadOnly, ReadWrite, WriteOnly};
+use self::Format::*;
+
+/// Whether a file can be read, written, or both.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub enum Access {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+/// What a file takes when written.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub enum Format {
+    /// An unsigned integer
+    U64,
+    /// A signed integer, where -1 is usually no limit
+    I64,
+    /// An integer within bounds, inclusive
+    Range(i64, i64),
+    /// An unsigned integer, or `max`
+    Limit,
+    /// A size in bytes with an optional unit, or no limit: `max` in v2 and -1 in v1
+    Size,
+    /// 0 or 1
+    Flag,
+    /// One of a few words
+    OneOf(&'static [&'static str]),
+    /// A list of ids and ranges of them, like `0-3,8`, or nothing
+    IdList,
+    /// A process or thread id
+    Pid,
+    /// A percentage with up to two decimals, or `max`
+    Percent,
+    /// A quota in microseconds or `max`, then optionally a period, as in `cpu.max`
+    CpuMax,
+    /// Anything, left for the kernel to check
+    Text,
+}
+
+impl Format {
+    /// Whether a value, without surrounding whitespace, is in this format.
+    pub fn check(&self, value: &str, version: Version) -> bool {
+        match *self {
+            Format::U64 => value.parse::<u64>().is_some(),
+            Format::I64 => value.parse::<i64>().is_some(),
+            Format::Range(min, max) => match value.parse::<i64>() {
+                Some(v) => v >= min && v <= max,
+                None => false,
+            },
+            Format::Limit => value == "max" || value.parse::<u64>().is_some(),
+            Format::Size => match version {
+                Version::V1 => value == "-1" || size::parse_bytes(value).is_some(),
+                Version::V2 => size::parse(value).is_some(),
+            },
+            Format::Flag => value == "0" || value == "1",
+            Format::OneOf(words) => words.iter().any(|&w| w == value),
+            Format::IdList => value.split(',').filter(|s| !s.is_empty()).all(|part| {
+                let mut ends = part.splitn(1, '-').map(|s| s.parse::<u32>());
+                match (ends.next(), ends.next()) {
+                    (Some(Some(_)), None) => true,
+                    (Some(Some(a)), Some(Some(b))) => a <= b,
+                    _ => false,
+                }
+            }),
+            Format::Pid => match value.parse::<i32>() {
+                Some(pid) => pid >= 0,
+                None => false,
+            },
+            Format::Percent => value == "max" || match value.parse::<f64>() {
+                Some(p) => p >= 0.0 && p <= 100.0,
+                None => false,
+            },
+            Format::CpuMax => {
+                let words: Vec<&str> = value.words().collect();
+                let quota = |&: w: &str| w == "max" || w.parse::<u64>().is_some();
+                match words.len() {
+                    1 => quota(words[0]),
+                    2 => quota(words[0]) && words[1].parse::<u64>().is_some(),
+                    _ => false,
+                }
+            },
+            Format::Text => true,
+        }
+    }
+}
+
+impl fmt::String for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Format::U64 => write!(f, "an unsigned integer"),
+            Format::I64 => write!(f, "an integer"),
+            Format::Range(min, max) => write!(f, "an integer from {} to {}", min, max),
+            Format::Limit => write!(f, "an unsigned integer or max"),
+            Format::Size => write!(f, "a size in bytes"),
+            Format::Flag => write!(f, "0 or 1"),
+            Format::OneOf(words) => write!(f, "one of {}", words.connect(", ")),
+            Format::IdList => write!(f, "a list of ids like 0-3,8"),
+            Format::Pid => write!(f, "a process id"),
+            Format::Percent => write!(f, "a percentage or max"),
+            Format::CpuMax => write!(f, "a quota or max, and an optional period"),
+            Format::Text => write!(f, "text"),
+        }
+    }
+}
+
+/// What the kernel documents of one control file.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub struct KeySchema {
+    pub name: &'static str,
+    pub access: Access,
+    pub format: Format,
+}
+
+static V1_KEYS: &'static [(&'static str, Access, Format)] = &[
+    ("cgroup.procs", ReadWrite, Pid),
+    ("cgroup.clone_children", ReadWrite, Flag),
+    ("cgroup.event_control", WriteOnly, Text),
+    ("cgroup.sane_behavior", ReadOnly, Text),
+    ("tasks", ReadWrite, Pid),
+    ("notify_on_release", ReadWrite, Flag),
+    ("release_agent", ReadWrite, Text),
+
+    ("blkio.weight", ReadWrite, Range(10, 1000)),
+    ("blkio.weight_device", ReadWrite, Text),
+    ("blkio.bfq.weight", ReadWrite, Text),
+    ("blkio.bfq.weight_device", ReadWrite, Text),
+    ("blkio.leaf_weight", ReadWrite, Range(10, 1000)),
+    ("blkio.leaf_weight_device", ReadWrite, Text),
+    ("blkio.throttle.read_bps_device", ReadWrite, Text),
+    ("blkio.throttle.write_bps_device", ReadWrite, Text),
+    ("blkio.throttle.read_iops_device", ReadWrite, Text),
+    ("blkio.throttle.write_iops_device", ReadWrite, Text),
+    ("blkio.throttle.io_service_bytes", ReadOnly, Text),
+    ("blkio.throttle.io_serviced", ReadOnly, Text),
+    ("blkio.io_service_bytes", ReadOnly, Text),
+    ("blkio.io_serviced", ReadOnly, Text),
+    ("blkio.io_service_time", ReadOnly, Text),
+    ("blkio.io_wait_time", ReadOnly, Text),
+    ("blkio.io_merged", ReadOnly, Text),
+    ("blkio.io_queued", ReadOnly, Text),
+    ("blkio.sectors", ReadOnly, Text),
+    ("blkio.time", ReadOnly, Text),
+    ("blkio.reset_stats", WriteOnly, Text),
+
+    ("cpu.shares", ReadWrite, Range(2, 262144)),
+    ("cpu.cfs_quota_us", ReadWrite, I64),
+    ("cpu.cfs_period_us", ReadWrite, Range(1000, 1000000)),
+    ("cpu.cfs_burst_us", ReadWrite, U64),
+    ("cpu.rt_runtime_us", ReadWrite, I64),
+    ("cpu.rt_period_us", ReadWrite, U64),
+    ("cpu.stat", ReadOnly, Text),
+    ("cpu.uclamp.min", ReadWrite, Percent),
+    ("cpu.uclamp.max", ReadWrite, Percent),
+    ("cpu.idle", ReadWrite, Flag),
+
+    ("cpuacct.usage", ReadWrite, U64),
+    ("cpuacct.usage_all", ReadOnly, Text),
+    ("cpuacct.usage_percpu", ReadOnly, Text),
+    ("cpuacct.usage_percpu_user", ReadOnly, Text),
+    ("cpuacct.usage_percpu_sys", ReadOnly, Text),
+    ("cpuacct.usage_user", ReadOnly, Text),
+    ("cpuacct.usage_sys", ReadOnly, Text),
+    ("cpuacct.stat", ReadOnly, Text),
+
+    ("cpuset.cpus", ReadWrite, IdList),
+    ("cpuset.mems", ReadWrite, IdList),
+    ("cpuset.effective_cpus", ReadOnly, Text),
+    ("cpuset.effective_mems", ReadOnly, Text),
+    ("cpuset.cpu_exclusive", ReadWrite, Flag),
+    ("cpuset.mem_exclusive", ReadWrite, Flag),
+    ("cpuset.mem_hardwall", ReadWrite, Flag),
+    ("cpuset.memory_migrate", ReadWrite, Flag),
+    ("cpuset.memory_pressure", ReadOnly, Text),
+    ("cpuset.memory_pressure_enabled", ReadWrite, Flag),
+    ("cpuset.memory_spread_page", ReadWrite, Flag),
+    ("cpuset.memory_spread_slab", ReadWrite, Flag),
+    ("cpuset.sched_load_balance", ReadWrite, Flag),
+    ("cpuset.sched_relax_domain_level", ReadWrite, Range(-1, 5)),
+
+    ("devices.allow", WriteOnly, Text),
+    ("devices.deny", WriteOnly, Text),
+    ("devices.list", ReadOnly, Text),
+
+    ("freezer.state", ReadWrite, OneOf(&["FROZEN", "THAWED"])),
+    ("freezer.self_freezing", ReadOnly, Text),
+    ("freezer.parent_freezing", ReadOnly, Text),
+
+    ("hugetlb.*.limit_in_bytes", ReadWrite, Size),
+    ("hugetlb.*.usage_in_bytes", ReadOnly, Text),
+    ("hugetlb.*.max_usage_in_bytes", ReadWrite, U64),
+    ("hugetlb.*.failcnt", ReadWrite, U64),
+    ("hugetlb.*.rsvd.limit_in_bytes", ReadWrite, Size),
+    ("hugetlb.*.rsvd.usage_in_bytes", ReadOnly, Text),
+    ("hugetlb.*.rsvd.max_usage_in_bytes", ReadWrite, U64),
+    ("hugetlb.*.rsvd.failcnt", ReadWrite, U64),
+    ("hugetlb.*.numa_stat", ReadOnly, Text),
+
+    ("memory.limit_in_bytes", ReadWrite, Size),
+    ("memory.soft_limit_in_bytes", ReadWrite, Size),
+    ("memory.memsw.limit_in_bytes", ReadWrite, Size),
+    ("memory.kmem.limit_in_bytes", ReadWrite, Size),
+    ("memory.kmem.tcp.limit_in_bytes", ReadWrite, Size),
+    ("memory.usage_in_bytes", ReadOnly, Text),
+    ("memory.memsw.usage_in_bytes", ReadOnly, Text),
+    ("memory.kmem.usage_in_bytes", ReadOnly, Text),
+    ("memory.kmem.tcp.usage_in_bytes", ReadOnly, Text),
+    ("memory.max_usage_in_bytes", ReadWrite, U64),
+    ("memory.memsw.max_usage_in_bytes", ReadWrite, U64),
+    ("memory.kmem.max_usage_in_bytes", ReadWrite, U64),
+    ("memory.failcnt", ReadWrite, U64),
+    ("memory.memsw.failcnt", ReadWrite, U64),
+    ("memory.kmem.failcnt", ReadWrite, U64),
+    ("memory.stat", ReadOnly, Text),
+    ("memory.numa_stat", ReadOnly, Text),
+    ("memory.swappiness", ReadWrite, Range(0, 200)),
+    ("memory.oom_control", ReadWrite, Flag),
+    ("memory.use_hierarchy", ReadWrite, Flag),
+    ("memory.move_charge_at_immigrate", ReadWrite, Range(0, 3)),
+    ("memory.force_empty", WriteOnly, Text),
+    ("memory.pressure_level", ReadOnly, Text),
+
+    ("net_cls.classid", ReadWrite, U64),
+    ("net_prio.prioidx", ReadOnly, Text),
+    ("net_prio.ifpriomap", ReadWrite, Text),
+
+    ("pids.max", ReadWrite, Limit),
+    ("pids.current", ReadOnly, Text),
+    ("pids.events", ReadOnly, Text),
+
+    ("rdma.max", ReadWrite, Text),
+    ("rdma.current", ReadOnly, Text),
+];
+
+static V2_KEYS: &'static [(&'static str, Access, Format)] = &[
+    ("cgroup.procs", ReadWrite, Pid),
+    ("cgroup.threads", ReadWrite, Pid),
+    ("cgroup.type", ReadWrite, OneOf(&["threaded"])),
+    ("cgroup.controllers", ReadOnly, Text),
+    ("cgroup.subtree_control", ReadWrite, Text),
+    ("cgroup.events", ReadOnly, Text),
+    ("cgroup.max.descendants", ReadWrite, Limit),
+    ("cgroup.max.depth", ReadWrite, Limit),
+    ("cgroup.stat", ReadOnly, Text),
+    ("cgroup.freeze", ReadWrite, Flag),
+    ("cgroup.kill", WriteOnly, OneOf(&["1"])),
+    ("cgroup.pressure", ReadWrite, Flag),
+
+    ("cpu.stat", ReadOnly, Text),
+    ("cpu.stat.local", ReadOnly, Text),
+    ("cpu.weight", ReadWrite, Range(1, 10000)),
+    ("cpu.weight.nice", ReadWrite, Range(-20, 19)),
+    ("cpu.max", ReadWrite, CpuMax),
+    ("cpu.max.burst", ReadWrite, U64),
+    ("cpu.uclamp.min", ReadWrite, Percent),
+    ("cpu.uclamp.max", ReadWrite, Percent),
+    ("cpu.idle", ReadWrite, Flag),
+    ("cpu.pressure", ReadWrite, Text),
+
+    ("cpuset.cpus", ReadWrite, IdList),
+    ("cpuset.mems", ReadWrite, IdList),
+    ("cpuset.cpus.effective", ReadOnly, Text),
+    ("cpuset.mems.effective", ReadOnly, Text),
+    ("cpuset.cpus.exclusive", ReadWrite, IdList),
+    ("cpuset.cpus.exclusive.effective", ReadOnly, Text),
+    ("cpuset.cpus.partition", ReadWrite, OneOf(&["member", "root", "isolated"])),
+
+    ("hugetlb.*.max", ReadWrite, Size),
+    ("hugetlb.*.current", ReadOnly, Text),
+    ("hugetlb.*.events", ReadOnly, Text),
+    ("hugetlb.*.events.local", ReadOnly, Text),
+    ("hugetlb.*.rsvd.max", ReadWrite, Size),
+    ("hugetlb.*.rsvd.current", ReadOnly, Text),
+    ("hugetlb.*.numa_stat", ReadOnly, Text),
+
+    ("io.stat", ReadOnly, Text),
+    ("io.weight", ReadWrite, Text),
+    ("io.bfq.weight", ReadWrite, Text),
+    ("io.max", ReadWrite, Text),
+    ("io.latency", ReadWrite, Text),
+    ("io.cost.qos", ReadWrite, Text),
+    ("io.cost.model", ReadWrite, Text),
+    ("io.prio.class", ReadWrite, OneOf(&["no-change", "promote-to-rt", "restrict-to-be",
+                                         "idle"])),
+    ("io.pressure", ReadWrite, Text),
+
+    ("memory.current", ReadOnly, Text),
+    ("memory.peak", ReadWrite, Text),
+    ("memory.min", ReadWrite, Size),
+    ("memory.low", ReadWrite, Size),
+    ("memory.high", ReadWrite, Size),
+    ("memory.max", ReadWrite, Size),
+    ("memory.reclaim", WriteOnly, Text),
+    ("memory.oom.group", ReadWrite, Flag),
+    ("memory.events", ReadOnly, Text),
+    ("memory.events.local", ReadOnly, Text),
+    ("memory.stat", ReadOnly, Text),
+    ("memory.numa_stat", ReadOnly, Text),
+    ("memory.swap.current", ReadOnly, Text),
+    ("memory.swap.peak", ReadWrite, Text),
+    ("memory.swap.high", ReadWrite, Size),
+    ("memory.swap.max", ReadWrite, Size),
+    ("memory.swap.events", ReadOnly, Text),
+    ("memory.zswap.current", ReadOnly, Text),
+    ("memory.zswap.max", ReadWrite, Size),
+    ("memory.zswap.writeback", ReadWrite, Flag),
+    ("memory.pressure", ReadWrite, Text),
+
+    ("misc.capacity", ReadOnly, Text),
+    ("misc.current", ReadOnly, Text),
+    ("misc.max", ReadWrite, Text),
+    ("misc.events", ReadOnly, Text),
+
+    ("pids.max", ReadWrite, Limit),
+    ("pids.current", ReadOnly, Text),
+    ("pids.peak", ReadOnly, Text),
+    ("pids.events", ReadOnly, Text),
+    ("pids.events.local", ReadOnly, Text),
+
+    ("rdma.max", ReadWrite, Text),
+    ("rdma.current", ReadOnly, Text),
+];
+
+/// Whether `key` matches a name from the tables, where a `*` stands for one component of the
+/// name: anything but a `.`, so `hugetlb.*.max` isn't taken for `hugetlb.2MB.rsvd.max`.
+fn matches(name: &str, key: &[u8]) -> bool {
+    let name = name.as_bytes();
+    match name.iter().position(|&c| c == b'*') {
+        None => name == key,
+        Some(star) => {
+            let (prefix, suffix) = (&name[..star], &name[star + 1..]);
+            key.len() > prefix.len() + suffix.len()
+                && key.starts_with(prefix) && key.ends_with(suffix)
+                && !key[prefix.len()..key.len() - suffix.len()].contains(&b'.')
+        },
+    }
+}
+
+/// Look up what's known of a file in a hierarchy.
+pub fn lookup(key: &[u8], version: Version) -> Option<KeySchema> {
+    let table = match version {
+        Version::V1 => V1_KEYS,
+        Version::V2 => V2_KEYS,
+    };
+    table.iter().find(|&&(name, _, _)| matches(name, key)).map(|&(name, access, format)| {
+        KeySchema { name: name, access: access, format: format }
+    })
+}
+
+impl Controller {
+    /// Check a write against the schema of this controller's hierarchy without touching the
+    /// kernel: an unknown key, a file that can't be written, and a value in the wrong format
+    /// are all `InvalidValue`, saying which. Passing doesn't promise the kernel will take it.
+    pub fn validate(&self, key: &[u8], value: &str) -> Result<()> {
+        let p = self.path().join(key);
+        let schema = match (lookup(key, self.version()), self.version()) {
+            (Some(s), _) => s,
+            (None, Version::V1) => {
+                return Err(Error::InvalidValue(p, "not a known v1 control file".to_string()));
+            },
+            (None, Version::V2) => {
+                return Err(Error::InvalidValue(p, "not a known v2 control file".to_string()));
+            },
+        };
+        if schema.access == Access::ReadOnly {
+            return Err(Error::InvalidValue(p, "read-only".to_string()));
+        }
+        let value = value.trim();
+        if !schema.format.check(value, self.version()) {
+            return Err(Error::InvalidValue(p, format!("{:?} is not {}", value, schema.format)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Version::{V1, V2};
+    use super::{lookup, Access, Format};
+    use super::Format::*;
+
+    fn accepts(format: Format, values: &[&str]) {
+        for v in values.iter() {
+            assert!(format.check(*v, V2), "{:?} should take {:?}", format, v);
+        }
+    }
+
+    fn rejects(format: Format, values: &[&str]) {
+        for v in values.iter() {
+            assert!(!format.check(*v, V2), "{:?} shouldn't take {:?}", format, v);
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        accepts(U64, &["0", "18446744073709551615"]);
+        rejects(U64, &["", "-1", "1.5", "max", "18446744073709551616"]);
+        accepts(I64, &["-1", "0", "42"]);
+        rejects(I64, &["", "max", "1k"]);
+        accepts(Range(-20, 19), &["-20", "0", "19"]);
+        rejects(Range(-20, 19), &["-21", "20", "x"]);
+        accepts(Limit, &["max", "0", "100"]);
+        rejects(Limit, &["-1", "MAX", "1G"]);
+        accepts(Flag, &["0", "1"]);
+        rejects(Flag, &["2", "true", ""]);
+        accepts(Pid, &["0", "1234"]);
+        rejects(Pid, &["-1", "abc", "4294967296"]);
+    }
+
+    #[test]
+    fn sizes() {
+        accepts(Size, &["max", "4096", "512M", "1.5G"]);
+        rejects(Size, &["-1", "", "1X", "M"]);
+        // v1 has -1 for no limit, and no max
+        assert!(Size.check("-1", V1));
+        assert!(Size.check("512M", V1));
+        assert!(!Size.check("max", V1));
+        assert!(!Size.check("-2", V1));
+    }
+
+    #[test]
+    fn id_lists() {
+        accepts(IdList, &["", "0", "0-3", "0-3,8", "1,2,3", "4-4", "0-3,,8"]);
+        rejects(IdList, &["3-1", "1-2-3", "-1", "0-", "a", "0-3,x", "1.5"]);
+    }
+
+    #[test]
+    fn words() {
+        let partition = OneOf(&["member", "root", "isolated"]);
+        accepts(partition, &["member", "isolated"]);
+        rejects(partition, &["", "Root", "root isolated"]);
+        accepts(Percent, &["max", "0", "12.34", "100"]);
+        rejects(Percent, &["-1", "100.5", "50%", ""]);
+        accepts(CpuMax, &["max", "50000", "max 100000", "50000 100000"]);
+        rejects(CpuMax, &["", "max max", "-1", "1 2 3", "50000 max"]);
+        accepts(Text, &["", "anything at all"]);
+    }
+
+    #[test]
+    fn lookups() {
+        let max = lookup(b"cpu.max", V2).unwrap();
+        assert_eq!((max.name, max.access, max.format), ("cpu.max", Access::ReadWrite, CpuMax));
+        assert!(lookup(b"cpu.max", V1).is_none());
+        assert!(lookup(b"memory.limit_in_bytes", V2).is_none());
+        assert!(lookup(b"cpu.maxx", V2).is_none());
+        assert_eq!(lookup(b"memory.current", V2).unwrap().access, Access::ReadOnly);
+    }
+
+    #[test]
+    fn hugetlb_page_sizes() {
+        assert_eq!(lookup(b"hugetlb.2MB.limit_in_bytes", V1).unwrap().name,
+                   "hugetlb.*.limit_in_bytes");
+        assert_eq!(lookup(b"hugetlb.2MB.rsvd.limit_in_bytes", V1).unwrap().name,
+                   "hugetlb.*.rsvd.limit_in_bytes");
+        assert_eq!(lookup(b"hugetlb.1GB.max", V2).unwrap().name, "hugetlb.*.max");
+        assert_eq!(lookup(b"hugetlb.1GB.rsvd.max", V2).unwrap().name, "hugetlb.*.rsvd.max");
+        assert_eq!(lookup(b"hugetlb.2MB.events.local", V2).unwrap().name,
+                   "hugetlb.*.events.local");
+        assert!(lookup(b"hugetlb..max", V2).is_none());
+        assert!(lookup(b"hugetlb.max", V2).is_none());
+    }
+}
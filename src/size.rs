@@ -0,0 +1,153 @@
+//! Sizes in bytes written the way people write them, like `512M` or `1.5GiB`.
+//!
+//! Units are powers of 1024 whether or not they have an `i`, as with the kernel's own
+//! `memparse` and with container runtimes; `K`, `KB` and `KiB` all mean 1024 bytes.
+
+use std::ascii::AsciiExt;
+use std::u64;
+
+use {Controller, Error, Limit, Result, Version};
+
+static UNITS: [&'static str; 6] = ["K", "M", "G", "T", "P", "E"];
+
+/// Parse a size in bytes, or `max` for no limit.
+pub fn parse(s: &str) -> Option<Limit> {
+    match s.trim() {
+        "max" => Some(Limit::Max),
+        s => parse_bytes(s).map(Limit::Value),
+    }
+}
+
+/// Parse a size in bytes, with an optional unit and fraction.
+pub fn parse_bytes(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s.find(|&: c: char| !(c.is_digit(10) || c == '.')).unwrap_or(s.len());
+    let (number, unit) = (&s[..split], s[split..].trim());
+    if number.is_empty() {
+        return None;
+    }
+    let shift = match unit.to_ascii_lowercase().as_slice() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        "t" | "tb" | "tib" => 40,
+        "p" | "pb" | "pib" => 50,
+        "e" | "eb" | "eib" => 60,
+        _ => return None,
+    };
+
+    if number.contains_char('.') {
+        let n = match number.parse::<f64>() {
+            Some(n) => n,
+            None => return None,
+        };
+        let bytes = n * (1u64 << shift) as f64;
+        if bytes >= u64::MAX as f64 { None } else { Some((bytes + 0.5) as u64) }
+    } else {
+        match number.parse::<u64>() {
+            Some(n) if n <= u64::MAX >> shift => Some(n << shift),
+            _ => None,
+        }
+    }
+}
+
+/// Format a size in bytes with the largest unit it has at least one of, like `512M` or
+/// `1.5G`, to one decimal place when it isn't a whole number of them.
+pub fn format(bytes: u64) -> String {
+    let mut shift = 0;
+    let mut unit = "";
+    for (i, &u) in UNITS.iter().enumerate() {
+        if bytes >= 1 << (10 * (i + 1)) {
+            shift = 10 * (i + 1);
+            unit = u;
+        }
+    }
+    if shift == 0 {
+        bytes.to_string()
+    } else if bytes % (1 << shift) == 0 {
+        format!("{}{}", bytes >> shift, unit)
+    } else {
+        format!("{:.1}{}", bytes as f64 / (1u64 << shift) as f64, unit)
+    }
+}
+
+/// Format a limit like `format`, or `max`.
+pub fn format_limit(limit: Limit) -> String {
+    match limit {
+        Limit::Max => "max".to_string(),
+        Limit::Value(bytes) => format(bytes),
+    }
+}
+
+impl Controller {
+    /// Set a limit in bytes from a size like `512M`, or `max` to remove it (written as -1 in
+    /// v1, where the memory files don't take `max`). Anything else is `InvalidValue`.
+    pub fn set_size(&self, key: &[u8], size: &str) -> Result<()> {
+        let value = match (parse(size), self.version()) {
+            (Some(Limit::Value(bytes)), _) => bytes.to_string(),
+            (Some(Limit::Max), Version::V1) => "-1".to_string(),
+            (Some(Limit::Max), Version::V2) => "max".to_string(),
+            (None, _) => {
+                return Err(Error::InvalidValue(self.path().join(key),
+                                               format!("{:?} is not a size", size)));
+            },
+        };
+        self.set(key, value.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::u64;
+
+    use Limit;
+    use super::{format, parse, parse_bytes};
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(parse_bytes("512"), Some(512));
+        assert_eq!(parse_bytes("512b"), Some(512));
+        assert_eq!(parse_bytes("1K"), Some(1024));
+        assert_eq!(parse_bytes("1kb"), Some(1024));
+        assert_eq!(parse_bytes(" 2 MiB "), Some(2 << 20));
+        assert_eq!(parse_bytes("3E"), Some(3 << 60));
+        assert_eq!(parse_bytes("1X"), None);
+        assert_eq!(parse_bytes("G"), None);
+        assert_eq!(parse_bytes(""), None);
+        assert_eq!(parse("max"), Some(Limit::Max));
+        assert_eq!(parse("4k"), Some(Limit::Value(4096)));
+    }
+
+    #[test]
+    fn parse_fractions() {
+        assert_eq!(parse_bytes("1.5G"), Some(1610612736));
+        assert_eq!(parse_bytes(".5G"), Some(536870912));
+        assert_eq!(parse_bytes("1.5K"), Some(1536));
+        // rounded to the nearest byte
+        assert_eq!(parse_bytes("1.5"), Some(2));
+        assert_eq!(parse_bytes("0.4"), Some(0));
+        assert_eq!(parse_bytes("1.2.3K"), None);
+    }
+
+    #[test]
+    fn parse_overflow() {
+        assert_eq!(parse_bytes("15E"), Some(15 << 60));
+        assert_eq!(parse_bytes("16E"), None);
+        assert_eq!(parse_bytes("16.0E"), None);
+        assert_eq!(parse_bytes("18446744073709551615"), Some(u64::MAX));
+        assert_eq!(parse_bytes("18446744073709551616"), None);
+        assert_eq!(parse_bytes("99999999999999999999999K"), None);
+    }
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format(0).as_slice(), "0");
+        assert_eq!(format(1023).as_slice(), "1023");
+        assert_eq!(format(1024).as_slice(), "1K");
+        assert_eq!(format(1536).as_slice(), "1.5K");
+        assert_eq!(format(512 << 20).as_slice(), "512M");
+        assert_eq!(format(1610612736).as_slice(), "1.5G");
+        assert_eq!(format(1 << 60).as_slice(), "1E");
+    }
+}
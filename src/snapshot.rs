@@ -0,0 +1,193 @@
+//! Dumping the whole state of a cgroup at once.
+
+use std::collections::BTreeMap;
+use std::i64;
+use std::io::fs::PathExtensions;
+use time;
+
+use {CGroup, Controller, Result, Version};
+use parse;
+
+/// Wall-clock time, in seconds and nanoseconds since the epoch.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Timestamp {
+    pub sec: i64,
+    pub nsec: i32,
+}
+
+impl Timestamp {
+    /// The current time.
+    pub fn now() -> Timestamp {
+        let t = time::get_time();
+        Timestamp { sec: t.sec, nsec: t.nsec }
+    }
+}
+
+/// The contents of every readable control file of a cgroup, read one after the other.
+#[derive(Clone, PartialEq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    /// The cgroup directory
+    pub path: String,
+    pub version: Version,
+    /// When reading started
+    pub time: Timestamp,
+    /// Monotonic clock in nanoseconds when reading started, for rates between snapshots
+    pub monotonic_ns: u64,
+    /// Contents of each control file, by name, without the trailing newline
+    pub values: BTreeMap<String, String>,
+    /// Names of the child cgroups
+    pub children: Vec<String>,
+}
+
+/// A control file whose contents differ between two snapshots.
+#[derive(Clone, PartialEq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Change {
+    pub before: String,
+    pub after: String,
+    /// How much each number went up (or down, when negative). A file holding a single number
+    /// has its delta under the empty key; flat-keyed files have one per key present in both.
+    pub deltas: BTreeMap<String, i64>,
+}
+
+/// What changed between two snapshots of the same cgroup.
+#[derive(Clone, PartialEq, Show, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnapshotDiff {
+    /// Monotonic nanoseconds between the two snapshots, 0 if the later one was taken first
+    pub elapsed_ns: u64,
+    /// Files only in the later snapshot, with their contents
+    pub added: BTreeMap<String, String>,
+    /// Files only in the earlier snapshot, with their contents
+    pub removed: BTreeMap<String, String>,
+    pub changed: BTreeMap<String, Change>,
+    pub added_children: Vec<String>,
+    pub removed_children: Vec<String>,
+}
+
+impl SnapshotDiff {
+    /// Whether the two snapshots had the same contents.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() &&
+            self.added_children.is_empty() && self.removed_children.is_empty()
+    }
+}
+
+impl Snapshot {
+    /// Compare this snapshot with a later one of the same cgroup. Swapping them gives the
+    /// changes backwards, with no time elapsed.
+    pub fn diff(&self, later: &Snapshot) -> SnapshotDiff {
+        let mut diff = SnapshotDiff::default();
+        diff.elapsed_ns = later.monotonic_ns.saturating_sub(self.monotonic_ns);
+
+        for (key, before) in self.values.iter() {
+            match later.values.get(key) {
+                None => { diff.removed.insert(key.clone(), before.clone()); },
+                Some(after) if after != before => {
+                    diff.changed.insert(key.clone(), Change {
+                        before: before.clone(),
+                        after: after.clone(),
+                        deltas: deltas(before.as_slice(), after.as_slice()),
+                    });
+                },
+                Some(_) => { },
+            }
+        }
+        for (key, after) in later.values.iter() {
+            if !self.values.contains_key(key) {
+                diff.added.insert(key.clone(), after.clone());
+            }
+        }
+
+        diff.added_children = later.children.iter().filter(|c| !self.children.contains(*c))
+                                   .cloned().collect();
+        diff.removed_children = self.children.iter().filter(|c| !later.children.contains(*c))
+                                    .cloned().collect();
+        diff
+    }
+}
+
+/// The numeric deltas between two versions of a file, empty if it doesn't hold numbers.
+/// Deltas too large for an i64 saturate.
+fn deltas(before: &str, after: &str) -> BTreeMap<String, i64> {
+    let mut deltas = BTreeMap::new();
+    let (before, after) = (before.trim(), after.trim());
+    if let (Some(b), Some(a)) = (before.parse::<u64>(), after.parse::<u64>()) {
+        deltas.insert(String::new(), delta(b, a));
+    } else if let (Some(b), Some(a)) = (before.parse::<i64>(), after.parse::<i64>()) {
+        deltas.insert(String::new(), a.saturating_sub(b));
+    } else if let (Some(b), Some(a)) = (parse::flat_keyed(before), parse::flat_keyed(after)) {
+        for (key, &old) in b.iter() {
+            if let Some(&new) = a.get(key) {
+                deltas.insert(key.clone(), delta(old, new));
+            }
+        }
+    }
+    deltas
+}
+
+/// `new - old` for unsigned counters, saturating at the ends of an i64.
+fn delta(old: u64, new: u64) -> i64 {
+    if new >= old {
+        if new - old > i64::MAX as u64 { i64::MAX } else { (new - old) as i64 }
+    } else {
+        if old - new > i64::MAX as u64 { i64::MIN } else { -((old - new) as i64) }
+    }
+}
+
+impl Controller {
+    /// Read every control file of this cgroup. Files that can't be read, like write-only ones
+    /// or ones that disappear meanwhile, are left out.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        let time = Timestamp::now();
+        let monotonic_ns = time::precise_time_ns();
+        let mut values = BTreeMap::new();
+        let mut children = Vec::new();
+
+        for entry in try!(::read_dir(self.path())).into_iter() {
+            let name = match entry.filename_str() {
+                Some(n) => n.to_string(),
+                None => continue,
+            };
+            if entry.is_dir() {
+                children.push(name);
+            } else if let Ok(contents) = ::read_file(&entry) {
+                let contents = if contents.ends_with("\n") {
+                    contents[..contents.len() - 1].to_string()
+                } else {
+                    contents
+                };
+                values.insert(name, contents);
+            }
+        }
+        children.sort();
+
+        Ok(Snapshot {
+            path: self.path().display().to_string(),
+            version: self.version(),
+            time: time,
+            monotonic_ns: monotonic_ns,
+            values: values,
+            children: children,
+        })
+    }
+}
+
+impl CGroup {
+    /// Snapshot this process's cgroup in every hierarchy, by hierarchy name (the controllers in
+    /// it, comma separated). The unified hierarchy is under the empty name.
+    pub fn snapshot_all(&self) -> Result<BTreeMap<String, Snapshot>> {
+        let mut all = BTreeMap::new();
+        for name in self.controllers.keys() {
+            let cont = try!(self.controller(name.as_slice()));
+            all.insert(String::from_utf8_lossy(name.as_slice()).into_owned(),
+                       try!(cont.snapshot()));
+        }
+        if self.unified.is_some() {
+            all.insert(String::new(), try!(try!(self.unified()).snapshot()));
+        }
+        Ok(all)
+    }
+}
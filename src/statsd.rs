@@ -0,0 +1,115 @@
+//! Pushing metrics to StatsD, or DogStatsD with tags, over UDP.
+
+use std::io::net::ip::{SocketAddr, ToSocketAddr};
+use std::io::net::udp::UdpSocket;
+
+use {Error, Result, Sample};
+use parse;
+
+/// Largest datagram to send, to stay below common MTUs.
+const MAX_PACKET: usize = 1432;
+
+/// A StatsD client. Metrics are named `<prefix>.<name>`; tags, given in DogStatsD's
+/// `|#key:value` syntax, are only sent when there are some.
+pub struct StatsdSink {
+    socket: UdpSocket,
+    addr: SocketAddr,
+    /// For errors, which are about the server rather than any file
+    path: Path,
+    prefix: String,
+    tags: Vec<(String, String)>,
+}
+
+impl StatsdSink {
+    /// Send to the server at `addr`, from an ephemeral local port.
+    pub fn new<A: ToSocketAddr>(addr: A, prefix: &str) -> Result<StatsdSink> {
+        let unresolved = Path::new("statsd");
+        let addr = try!(addr.to_socket_addr().map_err(|e| Error::Io(unresolved.clone(), e)));
+        let path = Path::new(addr.to_string());
+        let local = if addr.ip.to_string().contains_char(':') { "[::]:0" } else { "0.0.0.0:0" };
+        let socket = try!(UdpSocket::bind(local).map_err(|e| Error::Io(path.clone(), e)));
+        Ok(StatsdSink {
+            socket: socket,
+            addr: addr,
+            path: path,
+            prefix: prefix.to_string(),
+            tags: Vec::new(),
+        })
+    }
+
+    /// Add a tag to every metric sent.
+    pub fn tag(mut self, key: &str, value: &str) -> StatsdSink {
+        self.tags.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Send a gauge.
+    pub fn gauge(&mut self, name: &str, value: f64, tags: &[(&str, &str)]) -> Result<()> {
+        let line = self.line(name, value.to_string().as_slice(), "g", tags);
+        self.send(line.as_slice())
+    }
+
+    /// Send a counter increment.
+    pub fn count(&mut self, name: &str, delta: i64, tags: &[(&str, &str)]) -> Result<()> {
+        let line = self.line(name, delta.to_string().as_slice(), "c", tags);
+        self.send(line.as_slice())
+    }
+
+    /// Send every number in a sample as a gauge, tagged with its cgroup (so this needs a
+    /// server that understands DogStatsD tags): files holding one number as `<prefix>.<file>`,
+    /// and flat-keyed files like `memory.stat` as `<prefix>.<file>.<key>`. Lines are packed
+    /// into as few datagrams as fit.
+    pub fn send_sample(&mut self, sample: &Sample) -> Result<()> {
+        let mut packet = String::new();
+        for (dir, values) in sample.values.iter() {
+            let tags = [("cgroup", dir.as_slice())];
+            for (key, value) in values.iter() {
+                let mut lines = Vec::new();
+                if let Some(n) = value.as_slice().trim().parse::<u64>() {
+                    lines.push(self.line(key.as_slice(), n.to_string().as_slice(), "g", &tags));
+                } else if let Some(map) = parse::flat_keyed(value.as_slice()) {
+                    for (k, n) in map.iter() {
+                        let name = format!("{}.{}", key, k);
+                        lines.push(self.line(name.as_slice(), n.to_string().as_slice(), "g",
+                                             &tags));
+                    }
+                }
+                for line in lines.into_iter() {
+                    if !packet.is_empty() && packet.len() + 1 + line.len() > MAX_PACKET {
+                        try!(self.send(packet.as_slice()));
+                        packet.clear();
+                    }
+                    if !packet.is_empty() {
+                        packet.push('\n');
+                    }
+                    packet.push_str(line.as_slice());
+                }
+            }
+        }
+        if !packet.is_empty() {
+            try!(self.send(packet.as_slice()));
+        }
+        Ok(())
+    }
+
+    fn line(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) -> String {
+        let mut line = if self.prefix.is_empty() {
+            format!("{}:{}|{}", name, value, kind)
+        } else {
+            format!("{}.{}:{}|{}", self.prefix, name, value, kind)
+        };
+        let all: Vec<String> = self.tags.iter().map(|&(ref k, ref v)| format!("{}:{}", k, v))
+                                   .chain(tags.iter().map(|&(k, v)| format!("{}:{}", k, v)))
+                                   .collect();
+        if !all.is_empty() {
+            line.push_str("|#");
+            line.push_str(all.connect(",").as_slice());
+        }
+        line
+    }
+
+    fn send(&mut self, packet: &str) -> Result<()> {
+        let addr = self.addr;
+        self.socket.send_to(packet.as_bytes(), addr).map_err(|e| Error::Io(self.path.clone(), e))
+    }
+}
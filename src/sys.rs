@@ -0,0 +1,407 @@
+//! Thin wrappers over the system calls the standard library doesn't expose.
+//!
+//! Errors are classified from errno right where the call fails, so the kernel's meaning (EBUSY,
+//! EINVAL, ...) isn't lost on the way up.
+
+use std::ffi::CString;
+use std::io::{IoError, IoErrorKind};
+use std::mem;
+use std::os;
+use libc::{self, c_char, c_int, c_long, c_short, c_uint};
+
+use {Error, Result};
+
+// the generic value, which x86 and mips use too
+#[cfg(not(any(target_arch = "arm", target_arch = "aarch64", target_arch = "powerpc",
+              target_arch = "powerpc64")))]
+pub const O_DIRECTORY: c_int = 0o200000;
+#[cfg(any(target_arch = "arm", target_arch = "aarch64", target_arch = "powerpc",
+          target_arch = "powerpc64"))]
+pub const O_DIRECTORY: c_int = 0o40000;
+pub const O_CLOEXEC: c_int = 0o2000000;
+pub const O_PATH: c_int = 0o10000000;
+
+pub const EFD_CLOEXEC: c_int = O_CLOEXEC;
+
+pub const IN_CLOEXEC: c_int = O_CLOEXEC;
+pub const IN_MODIFY: u32 = 0x2;
+pub const IN_IGNORED: u32 = 0x8000;
+
+pub const O_NONBLOCK: c_int = 0o4000;
+pub const POLLIN: c_short = 0x1;
+pub const POLLPRI: c_short = 0x2;
+pub const POLLERR: c_short = 0x8;
+
+pub const EPOLL_CLOEXEC: c_int = O_CLOEXEC;
+const EPOLL_CTL_ADD: c_int = 1;
+const EPOLL_CTL_DEL: c_int = 2;
+pub const EPOLLIN: u32 = 0x1;
+pub const EPOLLPRI: u32 = 0x2;
+pub const EPOLLERR: u32 = 0x8;
+
+pub const MS_NOSUID: libc::c_ulong = 2;
+pub const MS_NODEV: libc::c_ulong = 4;
+pub const MS_NOEXEC: libc::c_ulong = 8;
+
+/// The same on every architecture, since clone3 came after the syscall tables were unified
+const SYS_CLONE3: c_long = 435;
+// bpf(2) isn't, so an architecture missing here leaves SYS_BPF undefined and the `bpf` feature
+// fails to build on it, rather than calling some other syscall
+#[cfg(all(feature = "bpf", target_arch = "x86_64"))]
+const SYS_BPF: c_long = 321;
+#[cfg(all(feature = "bpf", target_arch = "x86"))]
+const SYS_BPF: c_long = 357;
+#[cfg(all(feature = "bpf", any(target_arch = "aarch64", target_arch = "riscv64",
+                               target_arch = "loongarch64")))]
+const SYS_BPF: c_long = 280;
+#[cfg(all(feature = "bpf", target_arch = "arm"))]
+const SYS_BPF: c_long = 386;
+#[cfg(all(feature = "bpf", any(target_arch = "powerpc", target_arch = "powerpc64")))]
+const SYS_BPF: c_long = 361;
+#[cfg(all(feature = "bpf", target_arch = "s390x"))]
+const SYS_BPF: c_long = 351;
+#[cfg(all(feature = "bpf", any(target_arch = "mips", target_arch = "mipsel")))]
+const SYS_BPF: c_long = 4355;
+#[cfg(all(feature = "bpf", target_arch = "mips64"))]
+const SYS_BPF: c_long = 5315;
+#[cfg(all(feature = "bpf", target_arch = "sparc64"))]
+const SYS_BPF: c_long = 349;
+pub const CLONE_INTO_CGROUP: u64 = 0x200000000;
+pub const SIGCHLD: u64 = 17;
+const _SC_CLK_TCK: c_int = 2;
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+/// `struct clone_args`, as of Linux 5.7.
+#[repr(C)]
+#[derive(Default)]
+pub struct CloneArgs {
+    pub flags: u64,
+    pub pidfd: u64,
+    pub child_tid: u64,
+    pub parent_tid: u64,
+    pub exit_signal: u64,
+    pub stack: u64,
+    pub stack_size: u64,
+    pub tls: u64,
+    pub set_tid: u64,
+    pub set_tid_size: u64,
+    pub cgroup: u64,
+}
+
+/// `struct epoll_event`, which the kernel packs on x86_64.
+#[cfg_attr(target_arch = "x86_64", repr(C, packed))]
+#[cfg_attr(not(target_arch = "x86_64"), repr(C))]
+#[derive(Clone, Copy)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+extern {
+    fn epoll_create1(flags: c_int) -> c_int;
+    fn epoll_ctl(epfd: c_int, op: c_int, fd: c_int, event: *mut EpollEvent) -> c_int;
+    fn epoll_wait(epfd: c_int, events: *mut EpollEvent, maxevents: c_int, timeout: c_int)
+                  -> c_int;
+    fn syscall(num: c_long, ...) -> c_long;
+    fn sysconf(name: c_int) -> c_long;
+    fn pread(fd: c_int, buf: *mut libc::c_void, count: libc::size_t, offset: libc::off_t)
+             -> libc::ssize_t;
+    pub fn execvp(file: *const c_char, argv: *const *const c_char) -> c_int;
+    pub fn _exit(status: c_int) -> !;
+    pub fn waitpid(pid: libc::pid_t, status: *mut c_int, options: c_int) -> libc::pid_t;
+    pub static mut environ: *const *const c_char;
+    fn pipe2(fds: *mut c_int, flags: c_int) -> c_int;
+    fn openat(dirfd: c_int, pathname: *const c_char, flags: c_int, mode: libc::mode_t) -> c_int;
+    fn poll(fds: *mut PollFd, nfds: libc::c_ulong, timeout: c_int) -> c_int;
+    fn eventfd(initval: c_uint, flags: c_int) -> c_int;
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, pathname: *const libc::c_char, mask: u32) -> c_int;
+    fn mount(source: *const c_char, target: *const c_char, fstype: *const c_char,
+             flags: libc::c_ulong, data: *const libc::c_void) -> c_int;
+}
+
+/// Run a `bpf(2)` command on an attribute struct. Returns what the syscall does.
+#[cfg(feature = "bpf")]
+pub unsafe fn bpf<T>(cmd: c_int, attr: &mut T) -> c_long {
+    syscall(SYS_BPF, cmd, attr as *mut T, mem::size_of::<T>() as libc::c_uint)
+}
+
+/// Fork with `clone3(2)`. Returns 0 in the child, the child's pid in the parent, and -1 with
+/// errno set on failure.
+pub unsafe fn clone3(args: &mut CloneArgs) -> c_long {
+    syscall(SYS_CLONE3, args as *mut CloneArgs, mem::size_of::<CloneArgs>() as libc::size_t)
+}
+
+/// Clock ticks per second, the unit of `cpuacct.stat` and `/proc/<pid>/stat` times.
+pub fn clock_ticks() -> u64 {
+    match unsafe { sysconf(_SC_CLK_TCK) } {
+        // can't fail on Linux, where it's nearly always 100
+        n if n > 0 => n as u64,
+        _ => 100,
+    }
+}
+
+/// Create a directory.
+pub fn mkdir(path: &Path, mode: libc::mode_t) -> Result<()> {
+    let p = CString::from_slice(path.as_vec());
+    if unsafe { libc::mkdir(p.as_ptr(), mode) } < 0 {
+        return Err(Error::last_os_error(path));
+    }
+    Ok(())
+}
+
+/// Mount a filesystem, with its options in `data`.
+pub fn mount_fs(source: &str, target: &Path, fs_type: &str, flags: libc::c_ulong, data: &str)
+                -> Result<()> {
+    let source = CString::from_slice(source.as_bytes());
+    let p = CString::from_slice(target.as_vec());
+    let fs_type = CString::from_slice(fs_type.as_bytes());
+    let data = CString::from_slice(data.as_bytes());
+    let r = unsafe {
+        mount(source.as_ptr(), p.as_ptr(), fs_type.as_ptr(), flags,
+              data.as_ptr() as *const libc::c_void)
+    };
+    if r < 0 {
+        return Err(Error::last_os_error(target));
+    }
+    Ok(())
+}
+
+/// Change the owner and group of a file.
+pub fn chown(path: &Path, uid: libc::uid_t, gid: libc::gid_t) -> Result<()> {
+    let p = CString::from_slice(path.as_vec());
+    if unsafe { libc::chown(p.as_ptr(), uid, gid) } < 0 {
+        return Err(Error::last_os_error(path));
+    }
+    Ok(())
+}
+
+/// Remove a directory.
+pub fn rmdir(path: &Path) -> Result<()> {
+    let p = CString::from_slice(path.as_vec());
+    if unsafe { libc::rmdir(p.as_ptr()) } < 0 {
+        return Err(Error::last_os_error(path));
+    }
+    Ok(())
+}
+
+/// Whether this process may write to a file, as `access(2)` decides by its real ids.
+pub fn writable(path: &Path) -> bool {
+    let p = CString::from_slice(path.as_vec());
+    unsafe { libc::access(p.as_ptr(), libc::W_OK) == 0 }
+}
+
+/// An owned file descriptor, closed when dropped. It remembers the path it is about, for
+/// errors.
+pub struct Fd {
+    fd: c_int,
+    path: Path,
+}
+
+impl Fd {
+    /// Take ownership of a raw descriptor returned by a system call, turning -1 into the error
+    /// in errno.
+    pub fn from_raw(fd: c_int, path: &Path) -> Result<Fd> {
+        if fd < 0 {
+            Err(Error::last_os_error(path))
+        } else {
+            Ok(Fd { fd: fd, path: path.clone() })
+        }
+    }
+
+    /// Open a path with `open(2)` flags. `O_CLOEXEC` is always added.
+    pub fn open(path: &Path, flags: c_int) -> Result<Fd> {
+        let p = CString::from_slice(path.as_vec());
+        Fd::from_raw(unsafe { libc::open(p.as_ptr(), flags | O_CLOEXEC, 0) }, path)
+    }
+
+    /// A pipe, as its read and write ends. `path` is only for errors.
+    pub fn pipe(path: &Path) -> Result<(Fd, Fd)> {
+        let mut fds = [0 as c_int; 2];
+        if unsafe { pipe2(fds.as_mut_ptr(), O_CLOEXEC) } < 0 {
+            return Err(Error::last_os_error(path));
+        }
+        Ok((Fd { fd: fds[0], path: path.clone() }, Fd { fd: fds[1], path: path.clone() }))
+    }
+
+    /// Give up ownership of the descriptor without closing it.
+    pub fn into_raw(self) -> c_int {
+        let fd = self.fd;
+        unsafe { mem::forget(self); }
+        fd
+    }
+
+    /// Open a file relative to this directory descriptor with `openat(2)`. `O_CLOEXEC` is always
+    /// added.
+    pub fn openat(&self, name: &[u8], flags: c_int) -> Result<Fd> {
+        let path = self.path.join(name);
+        let p = CString::from_slice(name);
+        Fd::from_raw(unsafe { openat(self.fd, p.as_ptr(), flags | O_CLOEXEC, 0) }, &path)
+    }
+
+    /// Create an eventfd for notifications about `path`.
+    pub fn eventfd(path: &Path) -> Result<Fd> {
+        Fd::from_raw(unsafe { eventfd(0, EFD_CLOEXEC) }, path)
+    }
+
+    /// Read an eventfd's counter, blocking until it is non-zero.
+    pub fn read_u64(&self) -> Result<u64> {
+        let mut buf = 0u64;
+        loop {
+            let n = unsafe {
+                libc::read(self.fd, &mut buf as *mut u64 as *mut libc::c_void,
+                           mem::size_of::<u64>() as libc::size_t)
+            };
+            if n >= 0 {
+                return Ok(buf);
+            }
+            if os::errno() as c_int != libc::EINTR {
+                return Err(Error::last_os_error(&self.path));
+            }
+        }
+    }
+
+    /// Create an inotify instance watching one path for the events in `mask`.
+    pub fn inotify(path: &Path, mask: u32) -> Result<Fd> {
+        let fd = try!(Fd::from_raw(unsafe { inotify_init1(IN_CLOEXEC) }, path));
+        let p = CString::from_slice(path.as_vec());
+        if unsafe { inotify_add_watch(fd.fd, p.as_ptr(), mask) } < 0 {
+            return Err(Error::last_os_error(path));
+        }
+        Ok(fd)
+    }
+
+    /// Create an epoll instance. `path` is only for errors.
+    pub fn epoll(path: &Path) -> Result<Fd> {
+        Fd::from_raw(unsafe { epoll_create1(EPOLL_CLOEXEC) }, path)
+    }
+
+    /// Add a descriptor to this epoll instance, reporting `events` on it with `data`.
+    pub fn epoll_add(&self, fd: c_int, events: u32, data: u64) -> Result<()> {
+        let mut ev = EpollEvent { events: events, data: data };
+        if unsafe { epoll_ctl(self.fd, EPOLL_CTL_ADD, fd, &mut ev) } < 0 {
+            return Err(Error::last_os_error(&self.path));
+        }
+        Ok(())
+    }
+
+    /// Remove a descriptor from this epoll instance.
+    pub fn epoll_del(&self, fd: c_int) -> Result<()> {
+        let mut ev = EpollEvent { events: 0, data: 0 };
+        if unsafe { epoll_ctl(self.fd, EPOLL_CTL_DEL, fd, &mut ev) } < 0 {
+            return Err(Error::last_os_error(&self.path));
+        }
+        Ok(())
+    }
+
+    /// Wait for events on this epoll instance, for at most `timeout_ms` milliseconds (forever
+    /// if negative). Returns how many of `events` were filled in.
+    pub fn epoll_wait(&self, events: &mut [EpollEvent], timeout_ms: c_int) -> Result<usize> {
+        loop {
+            let n = unsafe {
+                epoll_wait(self.fd, events.as_mut_ptr(), events.len() as c_int, timeout_ms)
+            };
+            if n >= 0 {
+                return Ok(n as usize);
+            }
+            if os::errno() as c_int != libc::EINTR {
+                return Err(Error::last_os_error(&self.path));
+            }
+        }
+    }
+
+    /// Read whatever is available, blocking until something is.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            let n = unsafe {
+                libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void,
+                           buf.len() as libc::size_t)
+            };
+            if n >= 0 {
+                return Ok(n as usize);
+            }
+            if os::errno() as c_int != libc::EINTR {
+                return Err(Error::last_os_error(&self.path));
+            }
+        }
+    }
+
+    /// Read from an offset, leaving the file position alone.
+    pub fn pread(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        loop {
+            let n = unsafe {
+                pread(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len() as libc::size_t,
+                      offset as libc::off_t)
+            };
+            if n >= 0 {
+                return Ok(n as usize);
+            }
+            if os::errno() as c_int != libc::EINTR {
+                return Err(Error::last_os_error(&self.path));
+            }
+        }
+    }
+
+    /// Write a buffer in a single call. Control files take a value whole, so writing only part
+    /// of it is an error too.
+    pub fn write(&self, buf: &[u8]) -> Result<()> {
+        loop {
+            let n = unsafe {
+                libc::write(self.fd, buf.as_ptr() as *const libc::c_void,
+                            buf.len() as libc::size_t)
+            };
+            if n >= 0 && n as usize == buf.len() {
+                return Ok(());
+            }
+            if n >= 0 {
+                return Err(Error::Io(self.path.clone(), IoError {
+                    kind: IoErrorKind::ShortWrite(n as usize),
+                    desc: "short write",
+                    detail: Some(format!("{} of {} bytes written", n, buf.len())),
+                }));
+            }
+            if os::errno() as c_int != libc::EINTR {
+                return Err(Error::last_os_error(&self.path));
+            }
+        }
+    }
+
+    /// Wait for any of `events` on this descriptor, for at most `timeout_ms` milliseconds
+    /// (forever if negative). Returns the events that happened, empty on timeout.
+    pub fn poll(&self, events: c_short, timeout_ms: c_int) -> Result<c_short> {
+        let mut pfd = PollFd { fd: self.fd, events: events, revents: 0 };
+        loop {
+            match unsafe { poll(&mut pfd, 1, timeout_ms) } {
+                n if n < 0 => {
+                    if os::errno() as c_int != libc::EINTR {
+                        return Err(Error::last_os_error(&self.path));
+                    }
+                },
+                0 => return Ok(0),
+                _ => return Ok(pfd.revents),
+            }
+        }
+    }
+
+    /// The raw descriptor, still owned by this `Fd`.
+    pub fn raw(&self) -> c_int {
+        self.fd
+    }
+
+    /// The path this descriptor is about.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd); }
+    }
+}
@@ -0,0 +1,196 @@
+//! What the running kernel offers.
+
+use {CGroup, Controller, Error, Result, Version};
+use sys;
+
+/// A controller as listed in `/proc/cgroups`.
+#[derive(Clone, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ControllerInfo {
+    pub name: String,
+    /// The id of the v1 hierarchy it's bound to, 0 if it's in the unified hierarchy or unused
+    pub hierarchy: u32,
+    /// How many cgroups use it
+    pub num_cgroups: u64,
+    /// False if it was disabled at boot, with `cgroup_disable=`
+    pub enabled: bool,
+}
+
+/// Parse the contents of `/proc/cgroups`. None if it is malformed.
+pub fn parse_proc_cgroups(contents: &str) -> Option<Vec<ControllerInfo>> {
+    let mut controllers = Vec::new();
+    for line in contents.lines().filter(|l| !l.starts_with("#") && !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.words().collect();
+        if fields.len() != 4 {
+            return None;
+        }
+        match (fields[1].parse(), fields[2].parse(), fields[3]) {
+            (Some(h), Some(n), e) if e == "0" || e == "1" => controllers.push(ControllerInfo {
+                name: fields[0].to_string(),
+                hierarchy: h,
+                num_cgroups: n,
+                enabled: e == "1",
+            }),
+            _ => return None,
+        }
+    }
+    Some(controllers)
+}
+
+/// The controllers the kernel was built with, from `/proc/cgroups`.
+pub fn available_controllers() -> Result<Vec<ControllerInfo>> {
+    let p = Path::new("/proc/cgroups");
+    let contents = try!(::read_file(&p));
+    match parse_proc_cgroups(contents.as_slice()) {
+        Some(c) => Ok(c),
+        None => Err(Error::InvalidValue(p, "malformed /proc/cgroups".to_string())),
+    }
+}
+
+/// The machine's memory in bytes, the `MemTotal` of `/proc/meminfo`.
+pub fn memory_total() -> Result<u64> {
+    let p = Path::new("/proc/meminfo");
+    let contents = try!(::read_file(&p));
+    for line in contents.as_slice().lines().filter(|l| l.starts_with("MemTotal:")) {
+        let words: Vec<&str> = line.words().collect();
+        if words.len() == 3 && words[2] == "kB" {
+            if let Some(kb) = words[1].parse::<u64>() {
+                return Ok(kb * 1024);
+            }
+        }
+    }
+    Err(Error::InvalidValue(p, "no MemTotal in /proc/meminfo".to_string()))
+}
+
+/// Optional features of the unified hierarchy, found by looking for their files. The kernel
+/// versions are those that added them.
+///
+/// Controller files only exist where the controller is enabled, and the root cgroup has few
+/// of them, so a feature can look missing from a cgroup when the kernel has it.
+#[derive(Clone, PartialEq, Eq, Show, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Capabilities {
+    /// The unified hierarchy is mounted, with the probed cgroup in it
+    pub unified: bool,
+    /// Threaded cgroups, `cgroup.threads` (4.14)
+    pub threads: bool,
+    /// The v2 freezer, `cgroup.freeze` (5.2)
+    pub freeze: bool,
+    /// Killing a whole cgroup, `cgroup.kill` (5.14)
+    pub kill: bool,
+    /// Pressure stall information, `*.pressure` (4.20, when PSI is built in and not turned off)
+    pub psi: bool,
+    /// The pressure files can be written by this process, to set triggers
+    pub psi_triggers: bool,
+    /// PSI can be turned off for a cgroup, `cgroup.pressure` (6.5)
+    pub psi_toggle: bool,
+    /// `memory.oom.group` (4.19)
+    pub oom_group: bool,
+    /// `memory.reclaim` (5.19)
+    pub memory_reclaim: bool,
+    /// `memory.peak` (5.19)
+    pub memory_peak: bool,
+    /// `memory.zswap.max` (5.19)
+    pub zswap: bool,
+    /// `memory.zswap.writeback` (6.8)
+    pub zswap_writeback: bool,
+    /// `cpu.max.burst` (5.14)
+    pub cpu_burst: bool,
+    /// `cpu.idle` (5.15)
+    pub cpu_idle: bool,
+    /// `cpu.uclamp.min` and `cpu.uclamp.max` (5.3, when built in)
+    pub uclamp: bool,
+    /// `io.latency` (4.19)
+    pub io_latency: bool,
+    /// The iocost controller, `io.cost.qos` in the root cgroup (5.4)
+    pub iocost: bool,
+}
+
+impl Capabilities {
+    /// Probe this process's cgroup in the unified hierarchy. Without one, everything is false.
+    pub fn probe() -> Result<Capabilities> {
+        match try!(CGroup::new()).unified() {
+            Ok(cg) => Capabilities::probe_at(&cg),
+            Err(Error::ControllerNotMounted(_)) => Ok(Default::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Probe a v2 cgroup, and the root of its hierarchy for the features only found there. A
+    /// v1 cgroup has none of them.
+    pub fn probe_at(cg: &Controller) -> Result<Capabilities> {
+        if cg.version() != Version::V2 {
+            return Ok(Default::default());
+        }
+        let has = |&: key: &str| cg.has_key(key.as_bytes());
+        let pressure: Vec<Path> = ["cpu.pressure", "memory.pressure", "io.pressure"].iter()
+            .filter(|k| has(**k)).map(|k| cg.path().join(*k)).collect();
+        let iocost = match try!(cg.ancestors()).last() {
+            Some(root) => root.has_key(b"io.cost.qos"),
+            None => has("io.cost.qos"),
+        };
+
+        Ok(Capabilities {
+            unified: true,
+            threads: has("cgroup.threads"),
+            freeze: has("cgroup.freeze"),
+            kill: has("cgroup.kill"),
+            psi: !pressure.is_empty(),
+            psi_triggers: pressure.iter().any(|p| sys::writable(p)),
+            psi_toggle: has("cgroup.pressure"),
+            oom_group: has("memory.oom.group"),
+            memory_reclaim: has("memory.reclaim"),
+            memory_peak: has("memory.peak"),
+            zswap: has("memory.zswap.max"),
+            zswap_writeback: has("memory.zswap.writeback"),
+            cpu_burst: has("cpu.max.burst"),
+            cpu_idle: has("cpu.idle"),
+            uclamp: has("cpu.uclamp.min") && has("cpu.uclamp.max"),
+            io_latency: has("io.latency"),
+            iocost: iocost,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_proc_cgroups, ControllerInfo};
+
+    fn info(name: &str, hierarchy: u32, num_cgroups: u64, enabled: bool) -> ControllerInfo {
+        ControllerInfo {
+            name: name.to_string(),
+            hierarchy: hierarchy,
+            num_cgroups: num_cgroups,
+            enabled: enabled,
+        }
+    }
+
+    #[test]
+    fn proc_cgroups() {
+        let controllers = parse_proc_cgroups("\
+#subsys_name\thierarchy\tnum_cgroups\tenabled
+cpuset\t0\t112\t1
+cpu\t3\t112\t1
+memory\t0\t112\t0
+pids\t0\t112\t1
+").unwrap();
+        assert_eq!(controllers, vec![
+            info("cpuset", 0, 112, true),
+            info("cpu", 3, 112, true),
+            info("memory", 0, 112, false),
+            info("pids", 0, 112, true),
+        ]);
+        assert_eq!(parse_proc_cgroups("#subsys_name\thierarchy\tnum_cgroups\tenabled\n"),
+                   Some(Vec::new()));
+    }
+
+    #[test]
+    fn proc_cgroups_malformed() {
+        assert!(parse_proc_cgroups("cpu\t3\t112\n").is_none());
+        assert!(parse_proc_cgroups("cpu\t3\t112\t1\textra\n").is_none());
+        assert!(parse_proc_cgroups("cpu\tx\t112\t1\n").is_none());
+        assert!(parse_proc_cgroups("cpu\t3\t-1\t1\n").is_none());
+        assert!(parse_proc_cgroups("cpu\t3\t112\t2\n").is_none());
+        assert!(parse_proc_cgroups("cpu\t3\t112\t1\nmemory 0 112\n").is_none());
+    }
+}
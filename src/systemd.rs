@@ -0,0 +1,127 @@
+//! Letting systemd create cgroups, through its D-Bus API.
+//!
+//! On a systemd machine the manager owns the cgroup tree, and cgroups made behind its back can
+//! be moved or removed. Asking it for a transient scope or slice with `Delegate=yes` instead
+//! yields a subtree that is ours to manage. Only the unified hierarchy is supported.
+
+use std::io::{IoError, IoErrorKind};
+use std::io::fs::PathExtensions;
+use std::io::timer;
+use std::time::Duration;
+use dbus::{BusType, Connection, Message, MessageItem};
+use libc;
+
+use {CGroup, Controller, Error, Result, Version};
+use unit;
+
+const TIMEOUT_MS: i32 = 5000;
+
+/// A connection to a systemd manager.
+pub struct Systemd {
+    conn: Connection,
+    bus: &'static str,
+}
+
+impl Systemd {
+    /// Connect to the system manager, over the system bus.
+    pub fn system() -> Result<Systemd> {
+        Systemd::connect(BusType::System, "system bus")
+    }
+
+    /// Connect to the calling user's manager, over the session bus.
+    pub fn user() -> Result<Systemd> {
+        Systemd::connect(BusType::Session, "session bus")
+    }
+
+    fn connect(bus: BusType, name: &'static str) -> Result<Systemd> {
+        match Connection::get_private(bus) {
+            Ok(conn) => Ok(Systemd { conn: conn, bus: name }),
+            Err(e) => Err(bus_error(&Path::new("/sys/fs/cgroup"),
+                                    format!("connecting to the {}: {:?}", name, e.message()))),
+        }
+    }
+
+    /// Create a transient scope called `name` (ending in `.scope`) in `slice`, move `pids` into
+    /// it, and return a handle to its cgroup in the unified hierarchy.
+    pub fn start_scope(&self, cg: &CGroup, name: &str, slice: &str, pids: &[libc::pid_t])
+                       -> Result<Controller> {
+        let rel = unit::unit_path(name, slice).unwrap_or(Path::new(name));
+        let path = cg.unified_base().join(rel);
+        try!(check_unit_name(&path, name, ".scope"));
+        try!(check_unit_name(&path, slice, ".slice"));
+        if pids.is_empty() {
+            return Err(Error::InvalidValue(path, "a scope needs at least one process".to_string()));
+        }
+
+        let pids = pids.iter().map(|&p| MessageItem::UInt32(p as u32)).collect();
+        let props = vec![
+            property("Slice", MessageItem::Str(slice.to_string())),
+            property("Delegate", MessageItem::Bool(true)),
+            property("PIDs", MessageItem::Array(pids, "u".to_string())),
+        ];
+        try!(self.start_transient_unit(&path, name, props));
+        wait_for(path)
+    }
+
+    /// Create a transient slice called `name` (ending in `.slice`) and return a handle to its
+    /// cgroup in the unified hierarchy. Its parent follows from the name, as for any slice.
+    pub fn start_slice(&self, cg: &CGroup, name: &str) -> Result<Controller> {
+        let path = cg.unified_base().join(unit::slice_path(name).unwrap_or(Path::new(name)));
+        try!(check_unit_name(&path, name, ".slice"));
+        let props = vec![property("Delegate", MessageItem::Bool(true))];
+        try!(self.start_transient_unit(&path, name, props));
+        wait_for(path)
+    }
+
+    fn start_transient_unit(&self, path: &Path, name: &str, props: Vec<MessageItem>)
+                            -> Result<()> {
+        let mut msg = try!(Message::new_method_call("org.freedesktop.systemd1",
+                                                    "/org/freedesktop/systemd1",
+                                                    "org.freedesktop.systemd1.Manager",
+                                                    "StartTransientUnit")
+                           .map_err(|e| bus_error(path, e)));
+        msg.append_items(&[
+            MessageItem::Str(name.to_string()),
+            MessageItem::Str("fail".to_string()),
+            MessageItem::Array(props, "(sv)".to_string()),
+            MessageItem::Array(Vec::new(), "(sa(sv))".to_string()),
+        ]);
+        match self.conn.send_with_reply_and_block(msg, TIMEOUT_MS) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(bus_error(path, format!("StartTransientUnit on the {}: {}", self.bus,
+                                                  e.message().unwrap_or("no message")))),
+        }
+    }
+}
+
+fn check_unit_name(path: &Path, name: &str, suffix: &str) -> Result<()> {
+    if name.len() <= suffix.len() || !name.ends_with(suffix) || name.contains_char('/') {
+        return Err(Error::InvalidValue(path.clone(),
+                                       format!("{} is not a valid {} unit name", name, suffix)));
+    }
+    Ok(())
+}
+
+fn property(name: &str, value: MessageItem) -> MessageItem {
+    MessageItem::Struct(vec![MessageItem::Str(name.to_string()),
+                             MessageItem::Variant(Box::new(value))])
+}
+
+fn bus_error(path: &Path, what: String) -> Error {
+    Error::Io(path.clone(), IoError {
+        kind: IoErrorKind::OtherIoError,
+        desc: "systemd D-Bus call failed",
+        detail: Some(what),
+    })
+}
+
+/// systemd starts the unit asynchronously; wait (up to a second) for its cgroup to show up.
+fn wait_for(path: Path) -> Result<Controller> {
+    let procs = path.join("cgroup.procs");
+    let mut waited = 0;
+    while !procs.exists() && waited < 1000 {
+        timer::sleep(Duration::milliseconds(1));
+        waited += 1;
+    }
+    Controller::at(path, Version::V2)
+}
@@ -0,0 +1,148 @@
+//! Going through the cgroups below one.
+
+use std::collections::BTreeMap;
+use std::io::fs::PathExtensions;
+use std::vec;
+
+use libc;
+
+use {Controller, Error, Result, Version};
+
+/// The child cgroups of a cgroup, from `Controller::children`.
+pub struct Children {
+    dirs: vec::IntoIter<Path>,
+    version: Version,
+}
+
+impl Iterator for Children {
+    type Item = Result<Controller>;
+
+    fn next(&mut self) -> Option<Result<Controller>> {
+        for dir in self.dirs.by_ref() {
+            match Controller::at(dir, self.version) {
+                // removed since the directory was listed
+                Err(Error::KeyNotFound(_)) => continue,
+                r => return Some(r),
+            }
+        }
+        None
+    }
+}
+
+/// A cgroup found by `Controller::walk`.
+pub struct WalkEntry {
+    pub controller: Controller,
+    /// How far below the cgroup the walk started from, which is at depth 0
+    pub depth: usize,
+    /// The path from the cgroup the walk started from, `.` for itself
+    pub relative: Path,
+}
+
+/// A depth-first walk over a subtree, from `Controller::walk`.
+pub struct Walk<'a> {
+    root: Path,
+    /// Cgroups still to visit, with their depth; the next one is on top
+    stack: Vec<(Path, usize)>,
+    version: Version,
+    filter: Box<FnMut(&WalkEntry) -> bool + 'a>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Result<WalkEntry>> {
+        while let Some((dir, depth)) = self.stack.pop() {
+            let controller = match Controller::at(dir, self.version) {
+                Err(Error::KeyNotFound(_)) => continue,
+                Err(e) => return Some(Err(e)),
+                Ok(c) => c,
+            };
+            let relative = controller.path().path_relative_from(&self.root)
+                                     .unwrap_or_else(|| Path::new("."));
+            let entry = WalkEntry { controller: controller, depth: depth, relative: relative };
+            if !(self.filter)(&entry) {
+                continue;
+            }
+
+            let mut children = match entry.controller.children() {
+                Ok(c) => c.dirs.collect::<Vec<Path>>(),
+                Err(Error::KeyNotFound(_)) => Vec::new(),
+                Err(e) => return Some(Err(e)),
+            };
+            // pushed in reverse so they come off the stack in order of name
+            children.reverse();
+            self.stack.extend(children.into_iter().map(|c| (c, depth + 1)));
+            return Some(Ok(entry));
+        }
+        None
+    }
+}
+
+impl Controller {
+    /// Walk the subtree of this cgroup depth-first, starting with this cgroup itself and
+    /// visiting the children of each cgroup in order of name.
+    pub fn walk(&self) -> Walk<'static> {
+        self.walk_filtered(|_| true)
+    }
+
+    /// Like `walk`, leaving out every cgroup for which `filter` returns false along with
+    /// everything below it.
+    pub fn walk_filtered<'a, F>(&self, filter: F) -> Walk<'a>
+        where F: FnMut(&WalkEntry) -> bool + 'a
+    {
+        Walk {
+            root: self.path().clone(),
+            stack: vec![(self.path().clone(), 0)],
+            version: self.version(),
+            filter: Box::new(filter),
+        }
+    }
+
+    /// The processes anywhere in the subtree of this cgroup, in ascending order.
+    pub fn procs_recursive(&self) -> Result<Vec<libc::pid_t>> {
+        self.procs_by_cgroup().map(|procs| procs.into_iter().map(|(pid, _)| pid).collect())
+    }
+
+    /// The processes anywhere in the subtree of this cgroup, with the cgroup each is in. A
+    /// process that moves during the walk can be seen twice; it's listed where it was found
+    /// first.
+    pub fn procs_by_cgroup(&self) -> Result<BTreeMap<libc::pid_t, Path>> {
+        let mut procs = BTreeMap::new();
+        for entry in self.walk() {
+            let entry = try!(entry);
+            let pids = match ::read_procs(entry.controller.path()) {
+                Ok(p) => p,
+                // removed since the walk got to it
+                Err(Error::KeyNotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            for pid in pids.into_iter() {
+                if !procs.contains_key(&pid) {
+                    procs.insert(pid, entry.controller.path().clone());
+                }
+            }
+        }
+        Ok(procs)
+    }
+
+    /// The cgroups above this one, from its parent up to the root of the hierarchy.
+    pub fn ancestors(&self) -> Result<Vec<Controller>> {
+        let mut ancestors = Vec::new();
+        let mut dir = self.path().dir_path();
+        // the mount point is the last directory that's a cgroup
+        while dir.join("cgroup.procs").exists() {
+            let parent = dir.dir_path();
+            ancestors.push(try!(Controller::at(dir, self.version())));
+            dir = parent;
+        }
+        Ok(ancestors)
+    }
+
+    /// The child cgroups of this one, in order of name.
+    pub fn children(&self) -> Result<Children> {
+        let mut dirs: Vec<Path> = try!(::read_dir(self.path())).into_iter()
+                                      .filter(|p| p.is_dir()).collect();
+        dirs.sort();
+        Ok(Children { dirs: dirs.into_iter(), version: self.version() })
+    }
+}
@@ -0,0 +1,224 @@
+//! The core `cgroup.*` files of the unified hierarchy.
+
+use std::collections::BTreeSet;
+use std::collections::btree_set;
+use std::fmt;
+
+use {Controller, Error, Limit, Result, Version};
+use sys;
+
+/// What a cgroup's `cgroup.type` says it is.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CgroupType {
+    /// A normal cgroup, whose processes are managed as a whole
+    Domain,
+    /// The root of a threaded subtree
+    DomainThreaded,
+    /// A domain cgroup inside a threaded subtree, which can't be used until it's made threaded
+    DomainInvalid,
+    /// A member of a threaded subtree, whose threads can be placed individually
+    Threaded,
+}
+
+/// A set of controller names, as listed in `cgroup.controllers` and `cgroup.subtree_control`.
+#[derive(Clone, PartialEq, Eq, Show, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ControllerSet {
+    names: BTreeSet<Vec<u8>>,
+}
+
+impl ControllerSet {
+    /// Parse a space-separated list of controller names.
+    pub fn parse(s: &str) -> ControllerSet {
+        ControllerSet { names: s.words().map(|w| w.bytes().collect()).collect() }
+    }
+
+    /// Whether the named controller is in the set.
+    pub fn contains(&self, name: &[u8]) -> bool {
+        self.names.iter().any(|n| n.as_slice() == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// The names in the set, in order.
+    pub fn iter(&self) -> btree_set::Iter<Vec<u8>> {
+        self.names.iter()
+    }
+}
+
+impl fmt::String for ControllerSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, name) in self.names.iter().enumerate() {
+            if i > 0 { try!(write!(f, " ")); }
+            try!(write!(f, "{}", String::from_utf8_lossy(name.as_slice())));
+        }
+        Ok(())
+    }
+}
+
+impl Controller {
+    /// The controllers this cgroup's parent lets it use, from `cgroup.controllers`. Files of
+    /// other controllers don't exist here.
+    pub fn available_controllers(&self) -> Result<ControllerSet> {
+        self.get(b"cgroup.controllers").map(|s| ControllerSet::parse(s.as_slice()))
+    }
+
+    /// The controllers this cgroup hands down to its children, from `cgroup.subtree_control`.
+    pub fn delegated_controllers(&self) -> Result<ControllerSet> {
+        self.get(b"cgroup.subtree_control").map(|s| ControllerSet::parse(s.as_slice()))
+    }
+
+    /// The controllers enabled for this cgroup's children; the same as
+    /// `delegated_controllers`.
+    pub fn enabled_controllers(&self) -> Result<ControllerSet> {
+        self.delegated_controllers()
+    }
+
+    /// Enable a controller for this cgroup's children.
+    pub fn enable(&self, controller: &[u8]) -> Result<()> {
+        self.update_subtree_control(&[controller], &[])
+    }
+
+    /// Disable a controller for this cgroup's children.
+    pub fn disable(&self, controller: &[u8]) -> Result<()> {
+        self.update_subtree_control(&[], &[controller])
+    }
+
+    /// Enable and disable controllers for this cgroup's children in one write, like
+    /// `+cpu +memory -io`.
+    ///
+    /// A controller can only be enabled if this cgroup has it itself, that is, if the parent
+    /// enabled it; that's checked first so the failure says which controller is missing.
+    pub fn update_subtree_control(&self, enable: &[&[u8]], disable: &[&[u8]]) -> Result<()> {
+        try!(self.require_version(Version::V2, b"cgroup.subtree_control"));
+        let available = try!(self.available_controllers());
+        for name in enable.iter() {
+            if !available.contains(*name) {
+                return Err(Error::InvalidValue(self.path().join("cgroup.subtree_control"),
+                    format!("{} isn't enabled in the parent cgroup",
+                            String::from_utf8_lossy(*name))));
+            }
+        }
+
+        let mut line = Vec::new();
+        for name in enable.iter() {
+            if !line.is_empty() { line.push(b' '); }
+            line.push(b'+');
+            line.push_all(*name);
+        }
+        for name in disable.iter() {
+            if !line.is_empty() { line.push(b' '); }
+            line.push(b'-');
+            line.push_all(*name);
+        }
+        self.set_bytes(b"cgroup.subtree_control", line.as_slice())
+    }
+
+    /// This cgroup's type, from `cgroup.type`.
+    pub fn cgroup_type(&self) -> Result<CgroupType> {
+        let s = try!(self.get(b"cgroup.type"));
+        match s.as_slice().trim() {
+            "domain" => Ok(CgroupType::Domain),
+            "domain threaded" => Ok(CgroupType::DomainThreaded),
+            "domain invalid" => Ok(CgroupType::DomainInvalid),
+            "threaded" => Ok(CgroupType::Threaded),
+            _ => Err(self.invalid_value(b"cgroup.type", s.as_slice())),
+        }
+    }
+
+    /// Turn this cgroup into a threaded one. This can't be undone, and makes the parent the
+    /// root of a threaded subtree.
+    pub fn set_threaded(&self) -> Result<()> {
+        self.set(b"cgroup.type", "threaded")
+    }
+
+    /// Move a single thread into this cgroup, through the v1 `tasks` or the v2
+    /// `cgroup.threads`. In v2 the cgroup has to be part of a threaded subtree, which the
+    /// thread is in already; a domain cgroup is refused here with `InvalidValue` rather than
+    /// by the kernel with EOPNOTSUPP.
+    pub fn add_thread(&self, tid: ::libc::pid_t) -> Result<()> {
+        match self.version() {
+            Version::V1 => self.add_task(tid),
+            Version::V2 => {
+                match self.cgroup_type() {
+                    Ok(CgroupType::Threaded) | Ok(CgroupType::DomainThreaded) => {},
+                    // the root has no cgroup.type, and takes no single threads either
+                    Ok(_) | Err(Error::KeyNotFound(_)) => {
+                        return Err(Error::InvalidValue(self.path().join("cgroup.threads"),
+                            "threads can only be moved on their own into threaded cgroups"
+                                .to_string()));
+                    },
+                    Err(e) => return Err(e),
+                }
+                self.write_id(b"cgroup.threads", tid)
+            },
+        }
+    }
+
+    /// The threads in this cgroup, from the v1 `tasks` or the v2 `cgroup.threads`, in ascending
+    /// order like `procs`.
+    pub fn threads(&self) -> Result<Vec<::libc::pid_t>> {
+        match self.version() {
+            Version::V1 => self.tasks(),
+            Version::V2 => self.get_pids(b"cgroup.threads"),
+        }
+    }
+
+    /// How many descendant cgroups this cgroup may have, from `cgroup.max.descendants`.
+    pub fn max_descendants(&self) -> Result<Limit> {
+        self.get_limit(b"cgroup.max.descendants")
+    }
+
+    /// Limit how many descendant cgroups this cgroup may have. Creating more fails with an `Io`
+    /// error (EAGAIN).
+    pub fn set_max_descendants(&self, max: Limit) -> Result<()> {
+        self.set(b"cgroup.max.descendants", max.to_string().as_slice())
+    }
+
+    /// How many levels of cgroups may be below this one, from `cgroup.max.depth`.
+    pub fn max_depth(&self) -> Result<Limit> {
+        self.get_limit(b"cgroup.max.depth")
+    }
+
+    /// Limit how many levels of cgroups may be below this one, with the same failure as
+    /// `set_max_descendants` past it.
+    pub fn set_max_depth(&self, max: Limit) -> Result<()> {
+        self.set(b"cgroup.max.depth", max.to_string().as_slice())
+    }
+
+    /// Hand this cgroup over to a user, following the kernel's delegation rules: the directory
+    /// and the files listed in `/sys/kernel/cgroup/delegate` (`cgroup.procs`, `cgroup.threads`
+    /// and `cgroup.subtree_control` on kernels without it) are chowned to `uid` and `gid`.
+    ///
+    /// The user can then create children and move its own processes among them, but not raise
+    /// the limits set on this cgroup, whose other files stay owned by root.
+    pub fn delegate_to(&self, uid: ::libc::uid_t, gid: ::libc::gid_t) -> Result<()> {
+        try!(self.require_version(Version::V2, b"cgroup.subtree_control"));
+        let files = match ::read_file(&Path::new("/sys/kernel/cgroup/delegate")) {
+            Ok(list) => list.as_slice().lines().map(|l| l.trim().to_string())
+                            .filter(|l| !l.is_empty()).collect(),
+            Err(Error::KeyNotFound(_)) => vec!["cgroup.procs".to_string(),
+                                               "cgroup.threads".to_string(),
+                                               "cgroup.subtree_control".to_string()],
+            Err(e) => return Err(e),
+        };
+
+        try!(sys::chown(self.path(), uid, gid));
+        for file in files.iter() {
+            let p = self.path().join(file.as_slice());
+            // cgroup.threads only exists from 4.14, and newer entries only where enabled
+            match sys::chown(&p, uid, gid) {
+                Err(Error::KeyNotFound(_)) => { },
+                r => try!(r),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+//! The conventions systemd uses to name cgroups after its units.
+//!
+//! Every slice, scope and service gets a cgroup named after the unit, inside the cgroup of its
+//! slice: `foo.service` in `system.slice` lives at `system.slice/foo.service`, and the slice
+//! `a-b.slice` at `a.slice/a-b.slice`.
+
+use std::io::fs::PathExtensions;
+
+use {CGroup, Error, Result};
+
+/// Unit types that have a cgroup of their own.
+const CGROUP_SUFFIXES: &'static [&'static str] = &[".slice", ".scope", ".service", ".socket",
+                                                    ".mount", ".swap"];
+
+/// Escape a string for use in a unit name, like `systemd-escape`: `/` becomes `-`, and
+/// anything other than ASCII letters, digits, `:`, `_` and a non-leading `.` becomes `\xNN`.
+pub fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for (i, &b) in s.as_bytes().iter().enumerate() {
+        match b {
+            b'/' => out.push('-'),
+            b'.' if i > 0 => out.push('.'),
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b':' | b'_' => out.push(b as char),
+            _ => out.push_str(format!("\\x{:02x}", b).as_slice()),
+        }
+    }
+    out
+}
+
+/// Undo `escape`, None if an escape sequence is malformed or decodes to invalid UTF-8.
+pub fn unescape(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' => { out.push(b'/'); i += 1; },
+            b'\\' => {
+                if i + 4 > bytes.len() || bytes[i+1] != b'x' {
+                    return None;
+                }
+                let hex = match ::std::str::from_utf8(&bytes[i+2..i+4]) {
+                    Ok(h) => h,
+                    Err(_) => return None,
+                };
+                match ::std::num::from_str_radix(hex, 16) {
+                    Some(b) => out.push(b),
+                    None => return None,
+                }
+                i += 4;
+            },
+            b => { out.push(b); i += 1; },
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Escape a filesystem path the way `systemd-escape --path` does, for units like mounts that
+/// are named after one. The root is `-`.
+pub fn escape_path(path: &str) -> String {
+    let trimmed = path.trim_matches('/');
+    if trimmed.is_empty() {
+        "-".to_string()
+    } else {
+        escape(trimmed)
+    }
+}
+
+/// The cgroup directory of a slice, relative to the root of the hierarchy. None if `slice`
+/// isn't a slice name.
+pub fn slice_path(slice: &str) -> Option<Path> {
+    if !slice.ends_with(".slice") || slice.len() == ".slice".len() || slice.contains_char('/') {
+        return None;
+    }
+    let mut path = Path::new(".");
+    if slice == "-.slice" {
+        return Some(path);
+    }
+    let stem = &slice[..slice.len() - ".slice".len()];
+    let mut prefix = String::new();
+    for part in stem.split('-') {
+        if part.is_empty() {
+            return None;
+        }
+        if !prefix.is_empty() {
+            prefix.push('-');
+        }
+        prefix.push_str(part);
+        path = path.join(format!("{}.slice", prefix));
+    }
+    Some(path)
+}
+
+/// The cgroup directory of a unit placed in `slice`, relative to the root of the hierarchy.
+pub fn unit_path(unit: &str, slice: &str) -> Option<Path> {
+    if !is_cgroup_unit(unit) {
+        return None;
+    }
+    if unit.ends_with(".slice") {
+        return slice_path(unit);
+    }
+    slice_path(slice).map(|p| p.join(unit))
+}
+
+/// The innermost unit a cgroup directory belongs to, such as `foo.service` for
+/// `/system.slice/foo.service/worker`.
+pub fn unit_of(path: &Path) -> Option<String> {
+    let mut unit = None;
+    for c in path.str_components() {
+        if let Some(c) = c {
+            if is_cgroup_unit(c) {
+                unit = Some(c.to_string());
+            }
+        }
+    }
+    unit
+}
+
+fn is_cgroup_unit(name: &str) -> bool {
+    !name.contains_char('/') &&
+        CGROUP_SUFFIXES.iter().any(|s| name.len() > s.len() && name.ends_with(*s))
+}
+
+/// Look for a unit's cgroup under `root`: where it would be in `system.slice`, then anywhere.
+fn find_unit(root: &Path, unit: &str) -> Result<Option<Path>> {
+    if let Some(p) = unit_path(unit, "system.slice") {
+        if root.join(&p).is_dir() {
+            return Ok(Some(p));
+        }
+    }
+    search(root, &Path::new("."), unit)
+}
+
+fn search(root: &Path, rel: &Path, unit: &str) -> Result<Option<Path>> {
+    for child in try!(::read_dir(&root.join(rel))).into_iter() {
+        if !child.is_dir() {
+            continue;
+        }
+        let name = match child.filename_str() {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+        let child_rel = rel.join(name.as_slice());
+        if name.as_slice() == unit {
+            return Ok(Some(child_rel));
+        }
+        if let Some(found) = try!(search(root, &child_rel, unit)) {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+impl CGroup {
+    /// Get the CGroup of a systemd unit such as `foo.service`, in every hierarchy this process
+    /// sees, without asking systemd.
+    ///
+    /// The unit's cgroup is looked for in the unified hierarchy, or systemd's own `name=systemd`
+    /// hierarchy on v1-only systems; it's expected in `system.slice` but found anywhere.
+    pub fn for_unit(unit: &str) -> Result<CGroup> {
+        let mut cg = try!(CGroup::detect());
+        let root = if cg.unified.is_some() {
+            cg.unified_base()
+        } else {
+            // wherever the named hierarchy is mounted, `<basepath>/systemd` if it wasn't found
+            cg.hierarchy_base(b"name=systemd")
+        };
+        if !is_cgroup_unit(unit) {
+            return Err(Error::InvalidValue(root.join(unit),
+                                           "not a unit with a cgroup".to_string()));
+        }
+        let rel = match try!(find_unit(&root, unit)) {
+            Some(rel) => rel,
+            None => return Err(Error::KeyNotFound(root.join(unit))),
+        };
+
+        // cgroup paths start from the root of the namespace, not of the mount
+        let slash = Path::new("/");
+        for (name, path) in cg.controllers.iter_mut() {
+            *path = cg.roots.get(name).unwrap_or(&slash).join(&rel);
+        }
+        if cg.unified.is_some() {
+            cg.unified = Some(cg.unified_root.as_ref().unwrap_or(&slash).join(&rel));
+        }
+        Ok(cg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, escape_path, slice_path, unescape, unit_of, unit_path};
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("foo/bar-baz").as_slice(), "foo-bar\\x2dbaz");
+        assert_eq!(escape(".hidden/a.b c").as_slice(), "\\x2ehidden-a.b\\x20c");
+        assert_eq!(escape("user:1_x").as_slice(), "user:1_x");
+        assert_eq!(escape_path("/").as_slice(), "-");
+        assert_eq!(escape_path("/home/me/").as_slice(), "home-me");
+    }
+
+    #[test]
+    fn unescaping() {
+        assert_eq!(unescape("foo-bar\\x2dbaz"), Some("foo/bar-baz".to_string()));
+        assert_eq!(unescape("\\x2ehidden-a.b\\x20c"), Some(".hidden/a.b c".to_string()));
+        assert_eq!(unescape("a\\x2"), None);
+        assert_eq!(unescape("a\\y20"), None);
+        assert_eq!(unescape("a\\xzz"), None);
+        // a lone byte that isn't UTF-8
+        assert_eq!(unescape("a\\xff"), None);
+    }
+
+    #[test]
+    fn slice_paths() {
+        assert_eq!(slice_path("a-b-c.slice"), Some(Path::new("a.slice/a-b.slice/a-b-c.slice")));
+        assert_eq!(slice_path("system.slice"), Some(Path::new("system.slice")));
+        assert_eq!(slice_path("-.slice"), Some(Path::new(".")));
+        assert_eq!(slice_path("a--b.slice"), None);
+        assert_eq!(slice_path("-a.slice"), None);
+        assert_eq!(slice_path(".slice"), None);
+        assert_eq!(slice_path("a/b.slice"), None);
+        assert_eq!(slice_path("foo.service"), None);
+    }
+
+    #[test]
+    fn unit_paths() {
+        assert_eq!(unit_path("foo.service", "system.slice"),
+                   Some(Path::new("system.slice/foo.service")));
+        assert_eq!(unit_path("a-b.slice", "system.slice"), Some(Path::new("a.slice/a-b.slice")));
+        assert_eq!(unit_path("foo.target", "system.slice"), None);
+        assert_eq!(unit_of(&Path::new("/system.slice/foo.service/worker")),
+                   Some("foo.service".to_string()));
+        assert_eq!(unit_of(&Path::new("/init")), None);
+    }
+}
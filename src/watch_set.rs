@@ -0,0 +1,140 @@
+//! Watching many cgroups from one thread, with epoll.
+
+use std::collections::HashMap;
+use libc::c_int;
+
+use {Controller, Error, EventNotifier, FileWatcher, MemoryEvents, MemoryEventsWatcher, Result,
+     Trigger};
+use sys::{self, EpollEvent, Fd};
+
+/// Anything a `WatchSet` can watch.
+pub enum Watch {
+    File(FileWatcher),
+    MemoryEvents(MemoryEventsWatcher),
+    Notifier(EventNotifier),
+    Trigger(Trigger),
+}
+
+/// Something that happened to a watched cgroup.
+#[derive(Clone, Copy, PartialEq, Eq, Show)]
+pub enum Event {
+    /// A watched file was modified
+    Modified,
+    /// `memory.events` counters went up, by this much
+    MemoryEvents(MemoryEvents),
+    /// A `cgroup.event_control` notification, covering this many events
+    Notified(u64),
+    /// A PSI trigger fired
+    Pressure,
+    /// The cgroup was removed; the watch is dropped from the set
+    Gone,
+}
+
+impl Watch {
+    fn fd(&self) -> c_int {
+        match *self {
+            Watch::File(ref w) => w.fd(),
+            Watch::MemoryEvents(ref w) => w.fd(),
+            Watch::Notifier(ref w) => w.fd(),
+            Watch::Trigger(ref w) => w.fd(),
+        }
+    }
+
+    fn interest(&self) -> u32 {
+        match *self {
+            Watch::Trigger(_) => sys::EPOLLPRI,
+            _ => sys::EPOLLIN,
+        }
+    }
+
+    /// The pending event, if the descriptor's readiness wasn't spurious. `events` is what epoll
+    /// reported; PSI triggers have to go by it, having nothing left to read.
+    fn take(&mut self, events: u32) -> Result<Option<Event>> {
+        Ok(match *self {
+            Watch::File(ref w) => {
+                if try!(w.try_wait()) { Some(Event::Modified) } else { None }
+            },
+            Watch::MemoryEvents(ref mut w) => try!(w.try_wait()).map(Event::MemoryEvents),
+            Watch::Notifier(ref mut w) => try!(w.try_wait()).map(Event::Notified),
+            Watch::Trigger(ref w) => {
+                if try!(w.fired(events)) { Some(Event::Pressure) } else { None }
+            },
+        })
+    }
+}
+
+/// A token for a watch in a `WatchSet`, to remove it by.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Show)]
+pub struct Token(u64);
+
+/// Many watches sharing one epoll instance, each tagged with the cgroup it's about.
+pub struct WatchSet {
+    epoll: Fd,
+    watches: HashMap<u64, (Path, Watch)>,
+    next: u64,
+}
+
+impl WatchSet {
+    pub fn new() -> Result<WatchSet> {
+        Ok(WatchSet {
+            epoll: try!(Fd::epoll(&Path::new("epoll"))),
+            watches: HashMap::new(),
+            next: 0,
+        })
+    }
+
+    /// Watch something about a cgroup.
+    pub fn add(&mut self, cgroup: &Controller, watch: Watch) -> Result<Token> {
+        let token = self.next;
+        try!(self.epoll.epoll_add(watch.fd(), watch.interest(), token));
+        self.next += 1;
+        self.watches.insert(token, (cgroup.path().clone(), watch));
+        Ok(Token(token))
+    }
+
+    /// Stop watching, handing the watch back.
+    pub fn remove(&mut self, token: Token) -> Option<Watch> {
+        let (_, watch) = match self.watches.remove(&token.0) {
+            Some(w) => w,
+            None => return None,
+        };
+        let _ = self.epoll.epoll_del(watch.fd());
+        Some(watch)
+    }
+
+    /// How many watches there are.
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    /// Block until something happens to at least one watched cgroup, for at most `timeout_ms`
+    /// milliseconds (forever if negative), and return what happened to which cgroup. Empty on
+    /// timeout. Failing to read one watch doesn't stop the others from being reported.
+    pub fn wait(&mut self, timeout_ms: i32) -> Result<Vec<(Path, Result<Event>)>> {
+        let mut ready = [EpollEvent { events: 0, data: 0 }; 64];
+        let n = try!(self.epoll.epoll_wait(&mut ready, timeout_ms));
+
+        let mut events = Vec::new();
+        let mut gone = Vec::new();
+        for ev in ready[..n].iter() {
+            let token = ev.data;
+            let &mut (ref path, ref mut watch) = match self.watches.get_mut(&token) {
+                Some(w) => w,
+                None => continue,
+            };
+            match watch.take(ev.events) {
+                Ok(Some(e)) => events.push((path.clone(), Ok(e))),
+                Ok(None) => { },
+                Err(Error::KeyNotFound(_)) => {
+                    events.push((path.clone(), Ok(Event::Gone)));
+                    gone.push(Token(token));
+                },
+                Err(e) => events.push((path.clone(), Err(e))),
+            }
+        }
+        for token in gone.into_iter() {
+            self.remove(token);
+        }
+        Ok(events)
+    }
+}